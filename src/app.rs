@@ -4,21 +4,39 @@ use std::path::Path;
 use std::rc::Rc;
 
 use chrono::Local;
+use fltk::enums::Color;
 use fltk::prelude::*;
-use fltk::{app, dialog::{choice2, input}};
-use regex::Regex;
+use fltk::{app, dialog::{choice2, input}, menu::Choice};
+
+use gpt_text_read::backup::backup_included_files;
+use gpt_text_read::chunk::ChunkBudget;
+use gpt_text_read::export::{chunk_path, default_file_name, labelled_chunks, labelled_text};
+use gpt_text_read::fileops::check_patterns;
+use gpt_text_read::git::GitSelection;
+use gpt_text_read::models::{CollectOptions, PatternError, PatternKind, ProjectOutput};
+use gpt_text_read::linenum::{numbered_line, reference_at, LineReference};
+use gpt_text_read::patterns::{escape_glob, GLOB_PREFIX};
+use gpt_text_read::render::{render_with, OutputFormat, RenderContext};
+use gpt_text_read::tokens::token_report;
+use gpt_text_read::settings::{load_settings, write_settings};
+use gpt_text_read::snapdiff::{compare_snapshots, list_snapshots, SnapshotSource};
+use gpt_text_read::Collector;
 
-use crate::backup::backup_included_files;
-use crate::fileops::{collect_target_files, build_tree_view};
-use crate::models::ProjectOutput;
-use crate::settings::{load_settings, write_settings};
 use crate::ui::{UiMessage, build_ui, GuiComponents};
 
+/// トークン数をログに表示するファイル数
+const TOKEN_LOG_TOP: usize = 5;
+
+/// ファイル内容タブで各ファイルの先頭に付ける見出し
+const FILE_HEADER_PREFIX: &str = "File: ";
+
 /// アプリ全体でやり取りするデータ
 pub struct AppData {
     pub selected_project_dir: RefCell<Option<String>>,
     pub current_output_path: RefCell<Option<String>>,
     pub loaded_output: RefCell<Option<ProjectOutput>>,
+    // 設定ファイルの収集オプション（GUIで編集しない項目も保持して書き戻す）
+    pub current_options: RefCell<CollectOptions>,
 }
 
 /// アプリを起動する
@@ -30,6 +48,7 @@ pub fn run_app() {
         selected_project_dir: RefCell::new(None),
         current_output_path: RefCell::new(None),
         loaded_output: RefCell::new(None),
+        current_options: RefCell::new(CollectOptions::default()),
     });
 
     let mut gui = build_ui(app_data.clone());
@@ -63,6 +82,51 @@ pub fn run_app() {
                         // 出力先フォルダ
                         *app_data.current_output_path.borrow_mut() = loaded.output_path;
 
+                        // 設定ファイル内の不正なパターン
+                        for e in &loaded.pattern_errors {
+                            append_log(&gui, &format!("設定ファイルのパターンエラー: {}", e));
+                        }
+
+                        // 収集オプション
+                        gui.ignore_check.set_value(loaded.options.respect_ignore);
+                        gui.line_number_check.set_value(loaded.options.line_numbers);
+                        let format_index = OutputFormat::ALL
+                            .iter()
+                            .position(|f| *f == loaded.options.output_format)
+                            .unwrap_or(0);
+                        gui.format_choice.set_value(format_index as i32);
+                        let (git_index, git_ref) = match &loaded.options.git_selection {
+                            GitSelection::Off => (0, ""),
+                            GitSelection::WorkingTree => (1, ""),
+                            GitSelection::Since(r) => (2, r.as_str()),
+                            GitSelection::MergeBase(r) => (3, r.as_str()),
+                        };
+                        gui.git_choice.set_value(git_index);
+                        gui.git_ref_input.set_value(git_ref);
+                        let mut options = loaded.options;
+
+                        // 拒否リストの上書きは、その都度確認してから有効にする
+                        if !options.deny_overrides.is_empty() {
+                            let choice = choice2(
+                                0,
+                                0,
+                                &format!(
+                                    "設定ファイルが機密パスの拒否リストを上書きしようとしています。\n\n{}\n\n一致するファイルの読み込みを許可しますか？",
+                                    options.deny_overrides.join("\n")
+                                ),
+                                "許可しない", "許可する", ""
+                            );
+                            options.allow_denied = choice == Some(1);
+                            let state = if options.allow_denied { "許可" } else { "不許可" };
+                            append_log(
+                                &gui,
+                                &format!("拒否リストの上書き（{}）: {}", state, options.deny_overrides.join(", ")),
+                            );
+                        }
+                        *app_data.current_options.borrow_mut() = options;
+
+                        refresh_snapshot_choices(&mut gui, &folder_path);
+                        gui.snapdiff_buffer.borrow_mut().set_text("");
                         gui.sender.send(UiMessage::StartLoad);
                     }
                 }
@@ -84,47 +148,17 @@ pub fn run_app() {
                         }
 
                         let base_dir_opt = app_data.selected_project_dir.borrow().clone();
+                        let glob_mode = gui.glob_check.value();
                         for path in paths {
-                            if path.is_file() {
-                                let pattern = if let Some(ref base_str) = base_dir_opt {
-                                    let base_path = Path::new(base_str);
-                                    if let Ok(rel) = path.strip_prefix(base_path) {
-                                        let rel_str = rel.to_string_lossy().replace("\\", "/");
-                                        format!("^{}$", regex::escape(&rel_str))
-                                    } else {
-                                        let fname = path.file_name().unwrap().to_string_lossy().to_string();
-                                        format!("^{}$", regex::escape(&fname))
-                                    }
-                                } else {
-                                    let fname = path.file_name().unwrap().to_string_lossy().to_string();
-                                    format!("^{}$", regex::escape(&fname))
-                                };
-
-                                if !existing_patterns.contains(&pattern) {
-                                    existing_patterns.insert(pattern.clone());
-                                    current_text.push_str(&pattern);
-                                    current_text.push('\n');
-                                }
-                            } else if path.is_dir() {
-                                let pattern = if let Some(ref base_str) = base_dir_opt {
-                                    let base_path = Path::new(base_str);
-                                    if let Ok(rel) = path.strip_prefix(base_path) {
-                                        let rel_str = rel.to_string_lossy().replace("\\", "/");
-                                        format!("^{}.*$", regex::escape(&rel_str))
-                                    } else {
-                                        let dname = path.file_name().unwrap().to_string_lossy().to_string();
-                                        format!("^{}.*$", regex::escape(&dname))
-                                    }
-                                } else {
-                                    let dname = path.file_name().unwrap().to_string_lossy().to_string();
-                                    format!("^{}.*$", regex::escape(&dname))
-                                };
-
-                                if !existing_patterns.contains(&pattern) {
-                                    existing_patterns.insert(pattern.clone());
-                                    current_text.push_str(&pattern);
-                                    current_text.push('\n');
-                                }
+                            if !path.is_file() && !path.is_dir() {
+                                continue;
+                            }
+                            let pattern = picker_pattern(&path, base_dir_opt.as_deref(), glob_mode);
+
+                            if !existing_patterns.contains(&pattern) {
+                                existing_patterns.insert(pattern.clone());
+                                current_text.push_str(&pattern);
+                                current_text.push('\n');
                             }
                         }
 
@@ -147,8 +181,11 @@ pub fn run_app() {
                         }
                         if let Some(dir_name) = folder_path.file_name() {
                             let dname = dir_name.to_string_lossy().to_string();
-                            let escaped = regex::escape(&dname);
-                            let pattern = format!("^{}/.*$", escaped);
+                            let pattern = if gui.glob_check.value() {
+                                format!("{}{}/**", GLOB_PREFIX, escape_glob(&dname))
+                            } else {
+                                format!("^{}/.*$", regex::escape(&dname))
+                            };
                             current_text.push_str(&pattern);
                             current_text.push('\n');
                         }
@@ -183,6 +220,7 @@ pub fn run_app() {
                         let llm_lines: Vec<&str> = llm_text.lines().collect();
 
                         let output_dir_opt = app_data.current_output_path.borrow().clone();
+                        let options = current_options(&gui, &app_data);
 
                         if let Err(e) = write_settings(
                             dir,
@@ -191,82 +229,70 @@ pub fn run_app() {
                             &output_dir_opt,
                             &dev_lines,
                             &llm_lines,
+                            &options,
                         ) {
                             alert_default(&format!("設定保存に失敗しました: {}", e));
                             append_log(&gui, &format!("設定保存エラー: {}", e));
                         } else {
                             append_log(&gui, "設定ファイルを保存しました。");
                         }
+
+                        // 不正なパターンも保存はするが、件数を知らせる（詳細は読み込み時に表示）
+                        let errors = check_gui_patterns(&gui);
+                        if !errors.is_empty() {
+                            append_log(&gui, &format!("不正なパターンが {} 件あります。", errors.len()));
+                        }
                     }
                 }
 
                 UiMessage::StartLoad => {
+                    // 不正なパターンがあれば読み込まない（黙って無視するとファイルが消えて見えるため）
+                    let errors = check_gui_patterns(&gui);
+                    if !errors.is_empty() {
+                        report_pattern_errors(&gui, &errors);
+                        alert_default(&format!(
+                            "不正なパターンが {} 件あります。ログタブを確認して修正してください。",
+                            errors.len()
+                        ));
+                        append_log(&gui, "ファイル読み込み中止：不正なパターン");
+                        continue;
+                    }
+
                     append_log(&gui, "ファイル読み込み開始");
                     let dir_opt = app_data.selected_project_dir.borrow().clone();
                     let inc_text = gui.include_input.borrow().value();
                     let exc_text = gui.exclude_input.borrow().value();
                     let tree_on = *gui.tree_check_state.borrow();
+                    let options = current_options(&gui, &app_data);
+                    if !options.git_selection.is_off() {
+                        append_log(&gui, &format!("git の変更から選択: {}", options.git_selection.to_setting()));
+                    }
                     let sender = gui.sender.clone();
 
                     std::thread::spawn(move || {
                         if let Some(dir) = dir_opt {
-                            let inc_patterns: Vec<Regex> = inc_text
-                                .lines()
-                                .map(|s| s.trim())
-                                .filter(|s| !s.is_empty())
-                                .filter_map(|p| Regex::new(p).ok())
-                                .collect();
-
-                            let exc_patterns: Vec<Regex> = exc_text
-                                .lines()
-                                .map(|s| s.trim())
-                                .filter(|s| !s.is_empty())
-                                .filter_map(|p| Regex::new(p).ok())
+                            // llm_note は後でUIスレッド側で代入する
+                            let result = Collector::new(dir)
+                                .include(inc_text.lines())
+                                .exclude(exc_text.lines())
+                                .tree(tree_on)
+                                .options(options)
                                 .collect();
-
-                            match collect_target_files(&dir, &inc_patterns, &exc_patterns) {
-                                Ok(files) => {
-                                    let tree_view = if tree_on {
-                                        Some(build_tree_view(&dir, &exc_patterns))
-                                    } else {
-                                        None
-                                    };
-                                    // llm_note は後でUIスレッド側で代入する
-                                    let output = ProjectOutput {
-                                        files,
-                                        tree_view,
-                                        llm_note: None,
-                                    };
-                                    sender.send(UiMessage::LoadFinished(Ok(output)));
-                                }
-                                Err(e) => {
-                                    sender.send(UiMessage::LoadFinished(Err(e)));
-                                }
-                            }
+                            sender.send(UiMessage::LoadFinished(Box::new(result)));
                         }
                     });
                 }
 
                 UiMessage::LoadFinished(result) => {
-                    match result {
+                    match *result {
                         Ok(mut output) => {
                             let llm_txt = gui.llm_buffer.borrow().text();
                             output.llm_note = Some(llm_txt);
 
-                            let json_str = match serde_json::to_string_pretty(&output) {
-                                Ok(js) => js,
-                                Err(e) => {
-                                    alert_default(&format!("JSON変換に失敗: {}", e));
-                                    append_log(&gui, &format!("JSON変換エラー: {}", e));
-                                    continue;
-                                }
-                            };
-                            gui.json_buffer.borrow_mut().set_text(&json_str);
-
                             // 全ファイルの内容をまとめたテキスト
                             let mut all_text = String::new();
                             for file_info in &output.files {
-                                all_text.push_str("File: ");
+                                all_text.push_str(FILE_HEADER_PREFIX);
                                 all_text.push_str(&file_info.file_name);
                                 all_text.push('\n');
                                 all_text.push_str(&file_info.file_content);
@@ -281,10 +307,23 @@ pub fn run_app() {
                                 gui.tree_buffer.borrow_mut().set_text("");
                             }
 
-                            let size = json_str.len();
-                            gui.sender.send(UiMessage::UpdateCopySize(size));
+                            // スキップ・変換警告のあったファイル
+                            for w in &output.warnings {
+                                append_log(&gui, &format!("警告: {} ({})", w.file_name, w.reason));
+                            }
+                            if let Some(report) = &output.compaction {
+                                append_log(&gui, &format!("圧縮: {}", report.summary()));
+                            }
+                            if !output.diffs.is_empty() {
+                                append_log(&gui, &format!("差分: {} ファイル", output.diffs.len()));
+                            }
+                            // 伏せ字にした秘密情報（値は表示しない）
+                            for r in &output.redactions {
+                                append_log(&gui, &format!("伏せ字: {} {}行目 ({})", r.file_name, r.line, r.kind));
+                            }
 
                             *app_data.loaded_output.borrow_mut() = Some(output);
+                            show_rendered(&gui, &app_data);
 
                             append_log(&gui, "ファイル読み込み完了");
                         }
@@ -295,17 +334,61 @@ pub fn run_app() {
                     }
                 }
 
+                UiMessage::FormatChanged => {
+                    let format = current_options(&gui, &app_data).output_format;
+                    show_rendered(&gui, &app_data);
+                    append_log(&gui, &format!("出力形式: {}", format.label()));
+                }
+
+                UiMessage::OpenReference(pos) => {
+                    let text = gui.chosen_file_buffer.borrow().text();
+                    let Some(reference) = reference_at(&text, pos.max(0) as usize) else {
+                        continue;
+                    };
+                    match find_reference(&text, &reference) {
+                        Some((start, end)) => {
+                            gui.chosen_file_buffer.borrow_mut().select(start as i32, end as i32);
+                            gui.chosen_file_editor.set_insert_position(start as i32);
+                            gui.chosen_file_editor.show_insert_position();
+                            append_log(&gui, &format!("参照: {}:{}", reference.path, reference.line));
+                        }
+                        None => {
+                            append_log(&gui, &format!(
+                                "参照先が見つかりません: {}:{}",
+                                reference.path, reference.line
+                            ));
+                        }
+                    }
+                }
+
                 UiMessage::Copy => {
                     let val = gui.json_buffer.borrow().text();
                     app::copy(&val);
                     gui.sender.send(UiMessage::UpdateCopySize(val.len()));
                     append_log(&gui, &format!("JSONコピー ({} bytes)", val.len()));
+                    start_token_count(&gui, &app_data, val);
                 }
 
                 UiMessage::UpdateCopySize(size) => {
                     gui.copy_size_label.set_label(&format!("Copy Size: {}", size));
                 }
 
+                UiMessage::TokensCounted(report) => {
+                    gui.token_label.set_label(&format!("Tokens: {}", report.summary()));
+                    if report.over_budget() {
+                        gui.token_label.set_label_color(Color::Red);
+                        append_log(&gui, &format!("警告: トークン予算を超えています ({})", report.summary()));
+                    } else {
+                        gui.token_label.set_label_color(Color::Black);
+                    }
+                    gui.token_label.redraw();
+
+                    // トークン数の多いファイル（上位のみ）
+                    for (name, count) in report.per_file.iter().take(TOKEN_LOG_TOP) {
+                        append_log(&gui, &format!("  {} tokens: {}", count, name));
+                    }
+                }
+
                 UiMessage::ExportTxt => {
                     let val = gui.json_buffer.borrow().text();
                     if val.is_empty() {
@@ -326,17 +409,7 @@ pub fn run_app() {
                         ".".to_string()
                     };
 
-                    let time_str = now.format("%Y%m%d_%H%M%S").to_string();
-                    let default_file_name = if let Some(proj_dir) = &dir_opt {
-                        let folder_name = Path::new(proj_dir)
-                            .file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string();
-                        format!("{}_{}.txt", folder_name, time_str)
-                    } else {
-                        format!("output_{}.txt", time_str)
-                    };
+                    let default_file_name = default_file_name(dir_opt.as_deref(), &now);
 
                     if let Some(chosen_path) = rfd::FileDialog::new()
                         .set_directory(&dialog_dir)
@@ -351,143 +424,146 @@ pub fn run_app() {
                             }
                         }
 
-                        let total_size = val.len();
-                        const CHUNK_LIMIT: usize = 50_000;
-
-                        // ファイル先頭コメント
-                        let label = {
-                            let project_name = match dir_opt.as_ref() {
-                                Some(d) => {
-                                    Path::new(d)
-                                        .file_name()
-                                        .unwrap_or_default()
-                                        .to_string_lossy()
-                                        .to_string()
-                                },
-                                None => "NoProject".to_string(),
-                            };
-                            let date_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
-                            format!("// Project: {}, Date: {}\n", project_name, date_str)
-                        };
+                        // トークン数で測る場合は時間がかかるので別スレッドで測る
+                        let budget = ChunkBudget::from_options(&current_options(&gui, &app_data));
+                        let sender = gui.sender;
+                        std::thread::spawn(move || {
+                            let total_size = budget.measure(&val);
+                            sender.send(UiMessage::ExportMeasured(chosen_path, val, total_size));
+                        });
+                    }
+                }
 
-                        // LLM補足
-                        let llm_raw = gui.llm_buffer.borrow().text();
+                UiMessage::ExportMeasured(chosen_path, val, total_size) => {
+                    let dir_opt = app_data.selected_project_dir.borrow().clone();
+                    let now = Local::now();
 
-                        let labelled_val = format!(
-                            "// LLM補足:\n{}\n\n{}{}\n// End of chunk.\n",
-                            llm_raw,
-                            label,
-                            val
-                        );
+                    // LLM補足 + ファイル先頭コメント
+                    let llm_raw = gui.llm_buffer.borrow().text();
+                    let options = current_options(&gui, &app_data);
+                    // テンプレートは見出しも含めて出力するのでラベルを付けない
+                    let labelled_val = if options.output_format.is_self_labelled() {
+                        val.clone()
+                    } else {
+                        labelled_text(dir_opt.as_deref(), &llm_raw, &val, &now)
+                    };
 
-                        if total_size > CHUNK_LIMIT {
-                            let choice = choice2(
-                                0,
-                                0,
-                                &format!(
-                                    "JSONサイズが大きいです ({} bytes)。\nチャンク分割しますか？",
-                                    total_size
-                                ),
-                                "Yes", "No", ""
-                            );
+                    let budget = ChunkBudget::from_options(&options);
+                    // 分割はファイル境界で行うため、読み込み結果が必要
+                    let loaded = if total_size > budget.limit {
+                        app_data.loaded_output.borrow().clone()
+                    } else {
+                        None
+                    };
 
-                            match choice {
-                                Some(0) => {
-                                    // = Yes => 分割
-                                    match split_into_chunks(&labelled_val, CHUNK_LIMIT) {
-                                        Ok(chunks) => {
-                                            let stem = chosen_path.file_stem()
-                                                .unwrap_or_default()
-                                                .to_string_lossy()
-                                                .to_string();
-                                            let ext = chosen_path
-                                                .extension()
-                                                .unwrap_or_default()
-                                                .to_string_lossy()
-                                                .to_string();
-                                            for (i, ch) in chunks.iter().enumerate() {
-                                                let chunk_file_name = format!("{}_chunk_{}.{}", stem, i+1, ext);
-                                                let chunk_path = chosen_path
-                                                    .parent().unwrap_or_else(|| Path::new("."))
-                                                    .join(&chunk_file_name);
-
-                                                if let Err(e) = std::fs::write(&chunk_path, ch) {
-                                                    alert_default(&format!(
-                                                        "チャンクファイル書き込み失敗: {} ({})",
-                                                        e, chunk_path.display()
-                                                    ));
-                                                    append_log(&gui, &format!(
-                                                        "チャンクファイル書き込み失敗: {} ({})",
-                                                        e, chunk_path.display()
-                                                    ));
-                                                }
+                    if let Some(output) = loaded {
+                        let choice = choice2(
+                            0,
+                            0,
+                            &format!(
+                                "JSONサイズが大きいです ({} {} > {})。\nチャンク分割しますか？",
+                                total_size,
+                                budget.unit.name(),
+                                budget.limit
+                            ),
+                            "Yes", "No", ""
+                        );
+
+                        match choice {
+                            Some(0) => {
+                                // = Yes => ファイル境界で分割
+                                match labelled_chunks(
+                                    dir_opt.as_deref(),
+                                    &llm_raw,
+                                    &output,
+                                    &budget,
+                                    options.output_format,
+                                    &render_context(&gui, &app_data),
+                                    &now,
+                                ) {
+                                    Ok(chunks) => {
+                                        for (i, ch) in chunks.iter().enumerate() {
+                                            let chunk_path = chunk_path(&chosen_path, i + 1);
+                                            if let Err(e) = std::fs::write(&chunk_path, ch) {
+                                                alert_default(&format!(
+                                                    "チャンクファイル書き込み失敗: {} ({})",
+                                                    e, chunk_path.display()
+                                                ));
+                                                append_log(&gui, &format!(
+                                                    "チャンクファイル書き込み失敗: {} ({})",
+                                                    e, chunk_path.display()
+                                                ));
                                             }
-                                            append_log(&gui, "テキスト出力(チャンク分割)完了");
-                                        },
-                                        Err(e) => {
-                                            alert_default(&format!("チャンク分割エラー: {}", e));
-                                            append_log(&gui, &format!("チャンク分割エラー: {}", e));
                                         }
+                                        append_log(&gui, &format!(
+                                            "テキスト出力(チャンク分割 {} 個)完了",
+                                            chunks.len()
+                                        ));
+                                    },
+                                    Err(e) => {
+                                        alert_default(&format!("チャンク分割エラー: {}", e));
+                                        append_log(&gui, &format!("チャンク分割エラー: {}", e));
                                     }
-                                },
-                                Some(1) => {
-                                    // = No => 単一ファイル
-                                    if let Err(e) = std::fs::write(&chosen_path, labelled_val.as_bytes()) {
-                                        alert_default(&format!("書き込み失敗: {}", e));
-                                        append_log(&gui, &format!("テキスト書き込み失敗: {}", e));
-                                        continue;
-                                    }
-                                    append_log(&gui, &format!("テキスト出力完了: {}", chosen_path.display()));
-                                },
-                                _ => { /* キャンセル/クローズ => 何もしない */ }
-                            }
-                        } else {
-                            // チャンク不要
-                            if let Err(e) = std::fs::write(&chosen_path, labelled_val.as_bytes()) {
-                                alert_default(&format!("書き込み失敗: {}", e));
-                                append_log(&gui, &format!("テキスト書き込み失敗: {}", e));
-                                continue;
-                            }
-                            append_log(&gui, &format!("テキスト出力完了: {}", chosen_path.display()));
+                                }
+                            },
+                            Some(1) => {
+                                // = No => 単一ファイル
+                                if let Err(e) = std::fs::write(&chosen_path, labelled_val.as_bytes()) {
+                                    alert_default(&format!("書き込み失敗: {}", e));
+                                    append_log(&gui, &format!("テキスト書き込み失敗: {}", e));
+                                    continue;
+                                }
+                                append_log(&gui, &format!("テキスト出力完了: {}", chosen_path.display()));
+                            },
+                            _ => { /* キャンセル/クローズ => 何もしない */ }
                         }
-
-                        // 出力先を記憶
-                        if let Some(parent_dir) = chosen_path.parent() {
-                            let new_path_str = parent_dir.to_string_lossy().to_string();
-                            *app_data.current_output_path.borrow_mut() = Some(new_path_str);
+                    } else {
+                        // チャンク不要
+                        if let Err(e) = std::fs::write(&chosen_path, labelled_val.as_bytes()) {
+                            alert_default(&format!("書き込み失敗: {}", e));
+                            append_log(&gui, &format!("テキスト書き込み失敗: {}", e));
+                            continue;
                         }
+                        append_log(&gui, &format!("テキスト出力完了: {}", chosen_path.display()));
+                    }
 
-                        // OUTPUT_PATH保存
-                        let inc_text = gui.include_input.borrow().value();
-                        let exc_text = gui.exclude_input.borrow().value();
-                        let dev_text = gui.dev_memo_buffer.borrow().text();
-                        let inc_patterns: Vec<&str> = inc_text
-                            .lines()
-                            .map(|s| s.trim())
-                            .filter(|s| !s.is_empty())
-                            .collect();
-                        let exc_patterns: Vec<&str> = exc_text
-                            .lines()
-                            .map(|s| s.trim())
-                            .filter(|s| !s.is_empty())
-                            .collect();
-                        let dev_lines: Vec<&str> = dev_text.lines().collect();
-
-                        let llm_text = gui.llm_buffer.borrow().text();
-                        let llm_lines: Vec<&str> = llm_text.lines().collect();
+                    // 出力先を記憶
+                    if let Some(parent_dir) = chosen_path.parent() {
+                        let new_path_str = parent_dir.to_string_lossy().to_string();
+                        *app_data.current_output_path.borrow_mut() = Some(new_path_str);
+                    }
 
-                        if let Some(proj_dir) = &*app_data.selected_project_dir.borrow() {
-                            if let Err(e) = write_settings(
-                                proj_dir,
-                                &inc_patterns,
-                                &exc_patterns,
-                                &app_data.current_output_path.borrow(),
-                                &dev_lines,
-                                &llm_lines,
-                            ) {
-                                alert_default(&format!("OUTPUT_PATHの設定保存に失敗: {}", e));
-                                append_log(&gui, &format!("OUTPUT_PATHの設定保存に失敗: {}", e));
-                            }
+                    // OUTPUT_PATH保存
+                    let inc_text = gui.include_input.borrow().value();
+                    let exc_text = gui.exclude_input.borrow().value();
+                    let dev_text = gui.dev_memo_buffer.borrow().text();
+                    let inc_patterns: Vec<&str> = inc_text
+                        .lines()
+                        .map(|s| s.trim())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                    let exc_patterns: Vec<&str> = exc_text
+                        .lines()
+                        .map(|s| s.trim())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                    let dev_lines: Vec<&str> = dev_text.lines().collect();
+
+                    let llm_text = gui.llm_buffer.borrow().text();
+                    let llm_lines: Vec<&str> = llm_text.lines().collect();
+
+                    if let Some(proj_dir) = &*app_data.selected_project_dir.borrow() {
+                        if let Err(e) = write_settings(
+                            proj_dir,
+                            &inc_patterns,
+                            &exc_patterns,
+                            &app_data.current_output_path.borrow(),
+                            &dev_lines,
+                            &llm_lines,
+                            &current_options(&gui, &app_data),
+                        ) {
+                            alert_default(&format!("OUTPUT_PATHの設定保存に失敗: {}", e));
+                            append_log(&gui, &format!("OUTPUT_PATHの設定保存に失敗: {}", e));
                         }
                     }
                 }
@@ -507,8 +583,7 @@ pub fn run_app() {
                             let mut folder_comment = String::new();
                             let trimmed = input_str_opt.unwrap().trim().to_string();
                             if !trimmed.is_empty() {
-                                let re_alnum = Regex::new("^[A-Za-z0-9]+$").unwrap();
-                                if re_alnum.is_match(&trimmed) {
+                                if trimmed.chars().all(|c| c.is_ascii_alphanumeric()) {
                                     folder_comment = trimmed;
                                 } else {
                                     alert_default("英数字のみを入力してください。コメントは無視されます。");
@@ -519,6 +594,7 @@ pub fn run_app() {
                             match backup_included_files(base_dir, output, &folder_comment) {
                                 Ok(dest) => {
                                     append_log(&gui, &format!("スナップショット作成完了: {}", dest.display()));
+                                    refresh_snapshot_choices(&mut gui, base_dir);
                                 }
                                 Err(e) => {
                                     alert_default(&format!("バックアップ失敗: {}", e));
@@ -534,26 +610,257 @@ pub fn run_app() {
                         append_log(&gui, "バックアップ失敗：プロジェクト未選択");
                     }
                 }
+
+                UiMessage::CompareSnapshots => {
+                    let Some(base_dir) = app_data.selected_project_dir.borrow().clone() else {
+                        alert_default("プロジェクトフォルダが選択されていません。");
+                        continue;
+                    };
+                    let (Some(old), Some(new)) = (
+                        snapshot_source(&gui.snap_old_choice),
+                        snapshot_source(&gui.snap_new_choice),
+                    ) else {
+                        alert_default("比較するスナップショットを選択してください。");
+                        continue;
+                    };
+                    let options = current_options(&gui, &app_data);
+                    // 現在のファイル側は include・exclude で選んだファイル（上限等で外す前）
+                    let live = if old == SnapshotSource::Live || new == SnapshotSource::Live {
+                        let selected = Collector::new(base_dir.clone())
+                            .include(gui.include_input.borrow().value().lines())
+                            .exclude(gui.exclude_input.borrow().value().lines())
+                            .options(options.clone())
+                            .selected_files();
+                        match selected {
+                            Ok(files) => files,
+                            Err(e) => {
+                                alert_default(&format!("スナップショット比較失敗: {}", e));
+                                append_log(&gui, &format!("スナップショット比較失敗: {}", e));
+                                continue;
+                            }
+                        }
+                    } else {
+                        Vec::new()
+                    };
+                    match compare_snapshots(&base_dir, &old, &new, &live, &options) {
+                        Ok(diff) => {
+                            gui.snapdiff_buffer.borrow_mut().set_text(&diff.to_text());
+                            append_log(&gui, &format!("スナップショット比較 {} → {}: {}", diff.old, diff.new, diff.summary()));
+                        }
+                        Err(e) => {
+                            alert_default(&format!("スナップショット比較失敗: {}", e));
+                            append_log(&gui, &format!("スナップショット比較失敗: {}", e));
+                        }
+                    }
+                }
+
+                UiMessage::CopySnapshotDiff => {
+                    let val = gui.snapdiff_buffer.borrow().text();
+                    if val.is_empty() {
+                        alert_default("先に「比較」を実行してください。");
+                        continue;
+                    }
+                    app::copy(&val);
+                    append_log(&gui, &format!("スナップショット差分コピー ({} bytes)", val.len()));
+                }
+            }
+        }
+    }
+}
+
+/// スナップショット比較タブの選択肢を作り直す
+///
+/// 比較元はスナップショットのみ（既定: 最新）、比較先はスナップショットと「現在のファイル」（既定）。
+fn refresh_snapshot_choices(gui: &mut GuiComponents, base_dir: &str) {
+    let snapshots = list_snapshots(base_dir);
+    gui.snap_old_choice.clear();
+    gui.snap_new_choice.clear();
+    for name in &snapshots {
+        gui.snap_old_choice.add_choice(name);
+        gui.snap_new_choice.add_choice(name);
+    }
+    gui.snap_new_choice.add_choice(&SnapshotSource::Live.label());
+    gui.snap_old_choice.set_value(snapshots.len() as i32 - 1);
+    gui.snap_new_choice.set_value(snapshots.len() as i32);
+}
+
+/// 選択中の比較元・比較先（最後の「現在のファイル」は比較先にのみある）
+fn snapshot_source(choice: &Choice) -> Option<SnapshotSource> {
+    let label = choice.choice()?;
+    if label == SnapshotSource::Live.label() {
+        Some(SnapshotSource::Live)
+    } else {
+        Some(SnapshotSource::Snapshot(label))
+    }
+}
+
+/// ファイル/フォルダ選択ダイアログで選んだパスから Include パターンを作る
+///
+/// 正規表現モードではファイルは `^path$`、フォルダは `^path.*$`。
+/// グロブモードではファイルは `GLOB:path`、フォルダは `GLOB:path/**`（いずれもベースフォルダ起点）。
+fn picker_pattern(path: &Path, base_dir: Option<&str>, glob_mode: bool) -> String {
+    let rel_str = match base_dir.and_then(|b| path.strip_prefix(Path::new(b)).ok()) {
+        Some(rel) => rel.to_string_lossy().replace("\\", "/"),
+        None => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+    };
+    let is_dir = path.is_dir();
+
+    if glob_mode {
+        let escaped = escape_glob(&rel_str);
+        if is_dir {
+            format!("{}{}/**", GLOB_PREFIX, escaped)
+        } else if escaped.contains('/') {
+            format!("{}{}", GLOB_PREFIX, escaped)
+        } else {
+            // "/" を含まないグロブはどの階層にも一致するため、先頭に固定する
+            format!("{}/{}", GLOB_PREFIX, escaped)
+        }
+    } else if is_dir {
+        format!("^{}.*$", regex::escape(&rel_str))
+    } else {
+        format!("^{}$", regex::escape(&rel_str))
+    }
+}
+
+/// Include/Exclude 入力欄のパターンを検査する（行番号は入力欄の行）
+fn check_gui_patterns(gui: &GuiComponents) -> Vec<PatternError> {
+    let inc_text = gui.include_input.borrow().value();
+    let exc_text = gui.exclude_input.borrow().value();
+    let mut errors = check_patterns(PatternKind::Include, inc_text.lines());
+    errors.extend(check_patterns(PatternKind::Exclude, exc_text.lines()));
+    errors
+}
+
+/// パターンエラーをログに出し、最初のエラー行を入力欄で選択状態にする
+fn report_pattern_errors(gui: &GuiComponents, errors: &[PatternError]) {
+    for e in errors {
+        append_log(gui, &format!("パターンエラー: {}", e));
+    }
+
+    if let Some(first) = errors.first() {
+        let input = match first.kind {
+            PatternKind::Include => &gui.include_input,
+            PatternKind::Exclude => &gui.exclude_input,
+            PatternKind::Encoding
+            | PatternKind::Compact
+            | PatternKind::Outline
+            | PatternKind::Redact
+            | PatternKind::Deny
+            | PatternKind::Pin
+            | PatternKind::Setting => return,
+        };
+        let mut input = input.borrow_mut();
+        let text = input.value();
+
+        // 該当行のバイト範囲を求めて選択する
+        let mut start = 0;
+        for (i, line) in text.split('\n').enumerate() {
+            if i + 1 == first.line {
+                let end = start + line.len();
+                let _ = input.take_focus();
+                let _ = input.set_position(end as i32);
+                let _ = input.set_mark(start as i32);
+                break;
             }
+            start += line.len() + 1;
         }
     }
 }
 
-/// バイト数でナイーブに分割
-fn split_into_chunks(text: &str, chunk_size: usize) -> Result<Vec<String>, String> {
-    if chunk_size == 0 {
-        return Err("チャンクサイズが0です。".to_string());
+/// 読み込み結果を選択中の形式でテキストにして出力データタブに表示する
+fn show_rendered(gui: &GuiComponents, app_data: &AppData) {
+    let Some(output) = app_data.loaded_output.borrow().clone() else {
+        return;
+    };
+    let format = current_options(gui, app_data).output_format;
+    let text = match render_with(&output, format, &render_context(gui, app_data)) {
+        Ok(t) => t,
+        Err(e) => {
+            alert_default(&e);
+            append_log(gui, &format!("出力変換エラー: {}", e));
+            return;
+        }
+    };
+    gui.json_buffer.borrow_mut().set_text(&text);
+    gui.sender.send(UiMessage::UpdateCopySize(text.len()));
+
+    // トークン数は時間がかかるので別スレッドで数える
+    start_token_count(gui, app_data, text);
+}
+
+/// ファイル内容タブで `path:line` の参照先の行を探し、その行のバイト範囲を返す
+///
+/// タブは `File: パス` の見出しに続けて各ファイルの内容を並べたもの。
+/// 行番号付きならその番号で、なければ見出しからの行数で探す。
+fn find_reference(text: &str, reference: &LineReference) -> Option<(usize, usize)> {
+    let matches_path = |name: &str| {
+        name == reference.path
+            || name.ends_with(&format!("/{}", reference.path))
+            || reference.path.ends_with(&format!("/{}", name))
+    };
+
+    let mut in_file = false;
+    let mut line_in_file = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let body = line.trim_end_matches(['\r', '\n']);
+
+        if let Some(name) = body.strip_prefix(FILE_HEADER_PREFIX) {
+            in_file = matches_path(name);
+            line_in_file = 0;
+            continue;
+        }
+        if !in_file {
+            continue;
+        }
+        line_in_file += 1;
+        let number = numbered_line(body).unwrap_or(line_in_file);
+        if number == reference.line {
+            return Some((start, start + body.len()));
+        }
     }
-    let bytes = text.as_bytes();
-    let mut chunks = Vec::new();
-    let mut start = 0;
-    while start < bytes.len() {
-        let end = (start + chunk_size).min(bytes.len());
-        let slice = &bytes[start..end];
-        chunks.push(String::from_utf8_lossy(slice).to_string());
-        start += chunk_size;
+    None
+}
+
+/// テンプレート形式用の情報（プロジェクト・日時・開発メモ）
+fn render_context(gui: &GuiComponents, app_data: &AppData) -> RenderContext {
+    let dir_opt = app_data.selected_project_dir.borrow().clone();
+    let dev_memo = gui.dev_memo_buffer.borrow().text();
+    RenderContext::new(dir_opt.as_deref(), &dev_memo, &Local::now())
+}
+
+/// 読み込み結果と出力テキストのトークン数を別スレッドで数え、TokensCounted を送る
+fn start_token_count(gui: &GuiComponents, app_data: &AppData, rendered: String) {
+    let Some(output) = app_data.loaded_output.borrow().clone() else {
+        return;
+    };
+    let options = app_data.current_options.borrow().clone();
+    let sender = gui.sender;
+    std::thread::spawn(move || {
+        let report = token_report(&output, &rendered, options.token_encoding, options.token_budget);
+        sender.send(UiMessage::TokensCounted(report));
+    });
+}
+
+/// 保持している収集オプションに GUI のチェック状態を反映したもの
+fn current_options(gui: &GuiComponents, app_data: &AppData) -> CollectOptions {
+    let mut options = app_data.current_options.borrow().clone();
+    options.respect_ignore = gui.ignore_check.value();
+    options.line_numbers = gui.line_number_check.value();
+    if let Some(format) = OutputFormat::ALL.get(gui.format_choice.value().max(0) as usize) {
+        options.output_format = *format;
     }
-    Ok(chunks)
+    // ref が空なら include パターンで選ぶ
+    let git_ref = gui.git_ref_input.value().trim().to_string();
+    options.git_selection = match gui.git_choice.value() {
+        1 => GitSelection::WorkingTree,
+        2 if !git_ref.is_empty() => GitSelection::Since(git_ref),
+        3 if !git_ref.is_empty() => GitSelection::MergeBase(git_ref),
+        _ => GitSelection::Off,
+    };
+    options
 }
 
 fn alert_default(msg: &str) {
@@ -52,7 +52,7 @@ pub fn backup_included_files(
                 })?;
             }
 
-            fs::copy(&original_file_path, &dest_path).map_err(|e| {
+            fs::copy(original_file_path, &dest_path).map_err(|e| {
                 format!(
                     "ファイルコピーに失敗しました: {} (元: {:?}, 先: {:?})",
                     e, original_file_path, dest_path
@@ -0,0 +1,826 @@
+use serde::Serialize;
+
+use crate::models::{CollectOptions, FileDiff, FileInfo, ProjectOutput};
+use crate::render::{file_heading, render_with, OutputFormat, RenderContext};
+use crate::tokens::{count_tokens, TokenEncoding};
+
+/// チャンクの大きさを何で測るか
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkUnit {
+    #[default]
+    Bytes,
+    Tokens,
+}
+
+impl ChunkUnit {
+    /// 設定ファイルの値（`CHUNK_UNIT=`）から解釈する
+    pub fn from_name(name: &str) -> Option<ChunkUnit> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "bytes" | "byte" => Some(ChunkUnit::Bytes),
+            "tokens" | "token" => Some(ChunkUnit::Tokens),
+            _ => None,
+        }
+    }
+
+    /// 設定ファイルに書く名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChunkUnit::Bytes => "bytes",
+            ChunkUnit::Tokens => "tokens",
+        }
+    }
+
+    /// `CHUNK_LIMIT` 未指定時の大きさ
+    pub fn default_limit(&self) -> usize {
+        match self {
+            ChunkUnit::Bytes => 50_000,
+            ChunkUnit::Tokens => 12_000,
+        }
+    }
+}
+
+/// チャンク分割の目標サイズ
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkBudget {
+    pub limit: usize,
+    pub unit: ChunkUnit,
+    /// `unit` が Tokens のときに使うエンコーディング
+    pub encoding: TokenEncoding,
+}
+
+impl ChunkBudget {
+    /// 収集オプション（`CHUNK_LIMIT` / `CHUNK_UNIT` / `TOKEN_ENCODING`）から作る
+    pub fn from_options(options: &CollectOptions) -> ChunkBudget {
+        ChunkBudget {
+            limit: options.chunk_limit.unwrap_or_else(|| options.chunk_unit.default_limit()),
+            unit: options.chunk_unit,
+            encoding: options.token_encoding,
+        }
+    }
+
+    /// テキストの大きさを `unit` で測る
+    pub fn measure(&self, text: &str) -> usize {
+        match self.unit {
+            ChunkUnit::Bytes => text.len(),
+            ChunkUnit::Tokens => count_tokens(text, self.encoding),
+        }
+    }
+}
+
+/// 各チャンクの先頭に付ける自己記述情報
+#[derive(Clone, Debug, Serialize)]
+pub struct ChunkHeader {
+    pub part: usize,
+    pub total_parts: usize,
+    /// このチャンクに含まれるファイル（分割されたファイルは行範囲付き）
+    pub files: Vec<String>,
+}
+
+/// 分割された 1 チャンク
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    pub header: ChunkHeader,
+    /// このチャンク分の出力（llm_note・ツリー・warnings は最初のチャンクのみ）
+    pub output: ProjectOutput,
+}
+
+/// JSON 出力用（チャンク情報を先頭に置く）
+#[derive(Serialize)]
+struct ChunkDocument<'a> {
+    chunk: &'a ChunkHeader,
+    #[serde(flatten)]
+    output: &'a ProjectOutput,
+}
+
+impl Chunk {
+    /// チャンクを JSON にする
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(&chunk_document(self)).map_err(|e| format!("JSON変換に失敗: {}", e))
+    }
+
+    /// チャンクを指定の形式でテキストにする（JSON・テンプレートではチャンク情報も含める）
+    pub fn render(&self, format: OutputFormat, ctx: &RenderContext) -> Result<String, String> {
+        match format {
+            OutputFormat::Json => self.to_json(),
+            _ => {
+                let ctx = RenderContext {
+                    chunk: Some(self.header.clone()),
+                    ..ctx.clone()
+                };
+                render_with(&self.output, format, &ctx)
+            }
+        }
+    }
+
+    /// "// Part 1 of 3 (files: a, b)" のような見出し行
+    pub fn label(&self) -> String {
+        format!(
+            "// Part {} of {} (files: {})\n",
+            self.header.part,
+            self.header.total_parts,
+            self.header.files.join(", ")
+        )
+    }
+}
+
+/// チャンク情報の大きさを見積もるときの仮の番号（実際の番号より長くしておく）
+const PART_PLACEHOLDER: usize = 9999;
+
+/// チャンクに詰める単位（差分はハンク単位、ファイルは行単位で分けたもの）
+enum ChunkItem {
+    Diff(FileDiff),
+    File(FileInfo),
+}
+
+/// 出力を FileInfo の境界で分割する
+///
+/// 大きさは JSON にしたときで測る。各チャンクの見出し行とチャンク情報の分も差し引いて詰めるため、
+/// JSON にした 1 チャンクの大きさは `budget.limit` 以内に収まる（他の形式で収まるかどうかは
+/// `labelled_chunks` が実際に出力して確かめる）。
+/// 1 ファイルが目標サイズに収まらない場合は行単位で分け、1 行すら収まらない場合は
+/// 文字境界で分けるため、UTF-8 が壊れることはない。差分も同様にハンク・行単位で分ける。
+/// llm_note・ツリー・警告は最初のチャンクに入れ、ファイルと一緒に収まらなければ
+/// 最初のチャンクはそれだけにする。差分はファイルと同様に先頭から詰める。
+pub fn chunk_output(output: &ProjectOutput, budget: &ChunkBudget) -> Result<Vec<Chunk>, String> {
+    if budget.limit == 0 {
+        return Err("チャンクサイズが0です。".to_string());
+    }
+
+    // ファイル・差分を含まないチャンクの大きさ
+    let empty = ProjectOutput {
+        llm_note: None,
+        files: Vec::new(),
+        tree_view: None,
+        tree: None,
+        diffs: Vec::new(),
+        warnings: Vec::new(),
+        compaction: None,
+        redactions: Vec::new(),
+    };
+    let head = ProjectOutput {
+        files: Vec::new(),
+        diffs: Vec::new(),
+        ..output.clone()
+    };
+    let first_overhead = measure_chunk(&head, budget);
+    let rest_overhead = measure_chunk(&empty, budget);
+    let sizer = ItemSizer::new(empty, budget);
+
+    let mut items = Vec::new();
+    let mut diff_pieces = Vec::new();
+    for diff in &output.diffs {
+        split_diff(diff, budget.limit.saturating_sub(rest_overhead), &sizer, &mut diff_pieces);
+    }
+    for piece in diff_pieces {
+        let size = sizer.diff(&piece);
+        items.push((ChunkItem::Diff(piece), size));
+    }
+    let mut pieces = Vec::new();
+    for file in &output.files {
+        split_file(file, budget.limit.saturating_sub(rest_overhead), &sizer, &mut pieces);
+    }
+    for piece in pieces {
+        let size = sizer.file(&piece);
+        items.push((ChunkItem::File(piece), size));
+    }
+
+    // 貪欲に詰める
+    let mut groups: Vec<(Vec<FileDiff>, Vec<FileInfo>)> = Vec::new();
+    let mut current: (Vec<FileDiff>, Vec<FileInfo>) = (Vec::new(), Vec::new());
+    let mut current_size = first_overhead;
+    for (item, size) in items {
+        let has_items = !current.0.is_empty() || !current.1.is_empty();
+        // 最初のチャンクがツリー等で埋まっている場合は、それだけを 1 チャンクにする
+        let spill_head = groups.is_empty() && rest_overhead + size <= budget.limit;
+        if current_size + size > budget.limit && (has_items || spill_head) {
+            groups.push(std::mem::take(&mut current));
+            current_size = rest_overhead;
+        }
+        current_size += size;
+        match item {
+            ChunkItem::Diff(diff) => current.0.push(diff),
+            ChunkItem::File(file) => current.1.push(file),
+        }
+    }
+    if !current.0.is_empty() || !current.1.is_empty() || groups.is_empty() {
+        groups.push(current);
+    }
+
+    let total_parts = groups.len();
+    let chunks = groups
+        .into_iter()
+        .enumerate()
+        .map(|(i, (diffs, files))| {
+            let first = i == 0;
+            let header = ChunkHeader {
+                part: i + 1,
+                total_parts,
+                files: files.iter().map(file_heading).collect(),
+            };
+            let chunk_output = ProjectOutput {
+                llm_note: if first { output.llm_note.clone() } else { None },
+                files,
+                tree_view: if first { output.tree_view.clone() } else { None },
+                tree: if first { output.tree.clone() } else { None },
+                diffs,
+                warnings: if first { output.warnings.clone() } else { Vec::new() },
+                compaction: None,
+                redactions: Vec::new(),
+            };
+            Chunk {
+                header,
+                output: chunk_output,
+            }
+        })
+        .collect();
+    Ok(chunks)
+}
+
+/// JSON にしたときの大きさを測る
+fn measure_json<T: Serialize>(value: &T, budget: &ChunkBudget) -> usize {
+    serde_json::to_string_pretty(value)
+        .map(|s| budget.measure(&s))
+        .unwrap_or(0)
+}
+
+/// ファイルを含まないチャンクの、見出し行を含めた大きさ
+fn measure_chunk(output: &ProjectOutput, budget: &ChunkBudget) -> usize {
+    let chunk = Chunk {
+        header: ChunkHeader {
+            part: PART_PLACEHOLDER,
+            total_parts: PART_PLACEHOLDER,
+            files: Vec::new(),
+        },
+        output: output.clone(),
+    };
+    measure_json(&chunk_document(&chunk), budget) + budget.measure(&chunk.label())
+}
+
+fn chunk_document(chunk: &Chunk) -> ChunkDocument<'_> {
+    ChunkDocument {
+        chunk: &chunk.header,
+        output: &chunk.output,
+    }
+}
+
+/// 差分・ファイルを 1 つ足したときに増える大きさを測る
+///
+/// 空の出力に 1 つだけ入れたものとの差で測るので、JSON の字下げや区切りの分も含まれる。
+struct ItemSizer<'a> {
+    empty: ProjectOutput,
+    empty_size: usize,
+    budget: &'a ChunkBudget,
+}
+
+impl<'a> ItemSizer<'a> {
+    fn new(empty: ProjectOutput, budget: &'a ChunkBudget) -> ItemSizer<'a> {
+        let empty_size = measure_json(&empty, budget);
+        ItemSizer { empty, empty_size, budget }
+    }
+
+    fn with_one(&self, output: ProjectOutput) -> usize {
+        // 2 つ目以降は `,` が付く分も見込む
+        measure_json(&output, self.budget).saturating_sub(self.empty_size) + self.budget.measure(",")
+    }
+
+    fn diff(&self, diff: &FileDiff) -> usize {
+        self.with_one(ProjectOutput {
+            diffs: vec![diff.clone()],
+            ..self.empty.clone()
+        })
+    }
+
+    /// 見出し行（`name, `）とチャンク情報の files（字下げした `"name",`）に載る分も足す
+    fn file(&self, piece: &FileInfo) -> usize {
+        let heading = file_heading(piece);
+        let quoted = serde_json::to_string(&heading).unwrap_or_default();
+        self.with_one(ProjectOutput {
+            files: vec![piece.clone()],
+            ..self.empty.clone()
+        }) + self.budget.measure(&format!("{}, \n      {},", heading, quoted))
+    }
+}
+
+/// `limit` に収まらないファイルを行単位で分ける
+///
+/// 長い 1 行をまるごと数えるとトークン化が遅くなるため、大きさは常に行ごとに測る。
+fn split_file(file: &FileInfo, limit: usize, sizer: &ItemSizer, pieces: &mut Vec<FileInfo>) {
+    let budget = sizer.budget;
+    let first = pieces.len();
+    // ファイル名などの JSON 上のオーバーヘッドを除いた、本文に使える大きさ
+    // （見出しの「name (lines a-b)」は最も長くなる行番号で見込む）
+    let empty = FileInfo {
+        file_content: String::new(),
+        line_range: Some([999_999, 999_999]),
+        ..file.clone()
+    };
+    let content_limit = limit.saturating_sub(sizer.file(&empty)).max(1);
+
+    let make_piece = |content: String, start: usize, end: usize| FileInfo {
+        file_content: content,
+        line_range: Some([start, end]),
+        ..file.clone()
+    };
+
+    let mut buf = String::new();
+    let mut buf_size = 0;
+    let mut start_line = 1;
+    let mut line_no = 0;
+    for line in file.file_content.split_inclusive('\n') {
+        line_no += 1;
+        let size = measure_content(line, budget);
+
+        if !buf.is_empty() && buf_size + size > content_limit {
+            pieces.push(make_piece(std::mem::take(&mut buf), start_line, line_no - 1));
+            buf_size = 0;
+            start_line = line_no;
+        }
+
+        if size > content_limit {
+            // 1 行が長すぎる（minify されたファイル等）場合は文字境界で分ける
+            for part in split_long_line(line, content_limit, budget) {
+                pieces.push(make_piece(part, line_no, line_no));
+            }
+            start_line = line_no + 1;
+            continue;
+        }
+
+        buf.push_str(line);
+        buf_size += size;
+    }
+    if !buf.is_empty() || line_no == 0 {
+        pieces.push(make_piece(buf, start_line, line_no.max(start_line)));
+    }
+
+    // 分ける必要がなかったファイルは行範囲を付けずにそのまま
+    if pieces.len() == first + 1 {
+        pieces[first].line_range = None;
+    }
+}
+
+/// `limit` に収まらない差分をハンクの境界で分ける
+///
+/// 分けた各部分には `--- a/...` `+++ b/...` 等の見出し行を付け直す。
+/// 1 ハンクすら収まらない場合は `split_hunk` で行単位に分ける。
+fn split_diff(diff: &FileDiff, limit: usize, sizer: &ItemSizer, pieces: &mut Vec<FileDiff>) {
+    if sizer.diff(diff) <= limit {
+        pieces.push(diff.clone());
+        return;
+    }
+    let budget = sizer.budget;
+    let lines: Vec<&str> = diff.diff.lines().collect();
+    let body_start = lines.iter().position(|l| l.starts_with("@@")).unwrap_or(0);
+    let header = lines[..body_start].join("\n");
+    let make_piece = |body: &str| FileDiff {
+        diff: if header.is_empty() { body.to_string() } else { format!("{}\n{}", header, body) },
+        ..diff.clone()
+    };
+    // 見出し行等の JSON 上のオーバーヘッドを除いた、本文に使える大きさ
+    let content_limit = limit.saturating_sub(sizer.diff(&make_piece(""))).max(1);
+    let newline = measure_content("\n", budget);
+
+    let mut units = Vec::new();
+    for hunk in split_hunks(&lines[body_start..]) {
+        split_hunk(hunk, content_limit, budget, &mut units);
+    }
+
+    let mut body = String::new();
+    let mut body_size = 0;
+    for unit in units {
+        let size = measure_content(&unit, budget) + newline;
+        if !body.is_empty() && body_size + size > content_limit {
+            pieces.push(make_piece(&body));
+            body.clear();
+            body_size = 0;
+        }
+        if !body.is_empty() {
+            body.push('\n');
+        }
+        body.push_str(&unit);
+        body_size += size;
+    }
+    if !body.is_empty() {
+        pieces.push(make_piece(&body));
+    }
+}
+
+/// 差分の本文を `@@` 行ごとのハンクに分ける（`@@` 行がなければ全体で 1 つ）
+fn split_hunks<'a>(lines: &'a [&'a str]) -> Vec<&'a [&'a str]> {
+    let mut hunks = Vec::new();
+    let mut start = 0;
+    for (i, line) in lines.iter().enumerate().skip(1) {
+        if line.starts_with("@@") {
+            hunks.push(&lines[start..i]);
+            start = i;
+        }
+    }
+    if start < lines.len() {
+        hunks.push(&lines[start..]);
+    }
+    hunks
+}
+
+/// 1 ハンクを `limit` 以内の部分に分ける
+///
+/// 分けた部分ごとに `@@ -a,b +c,d @@` の行番号と行数を付け直すので、それぞれが正しいハンクになる。
+/// 1 行すら収まらない場合は文字境界で分け、続きの部分は `@@` 行なしで続ける。
+fn split_hunk(hunk: &[&str], limit: usize, budget: &ChunkBudget, units: &mut Vec<String>) {
+    let text = hunk.join("\n");
+    if measure_content(&text, budget) <= limit {
+        units.push(text);
+        return;
+    }
+    let newline = measure_content("\n", budget);
+    let (mut header, body) = match hunk.first().and_then(|h| HunkHeader::parse(h)) {
+        Some(h) => (Some(h), &hunk[1..]),
+        None => (None, hunk),
+    };
+    // 付け直す `@@` 行の分（最も長くなる行番号で見込む）を除いた大きさ
+    let header_size = header.as_ref().map_or(0, |h| measure_content(&h.placeholder(), budget) + newline);
+    let line_limit = limit.saturating_sub(header_size).max(1);
+
+    let mut piece: Vec<&str> = Vec::new();
+    let mut piece_size = 0;
+    for &line in body {
+        let size = measure_content(line, budget) + newline;
+        if !piece.is_empty() && piece_size + size > line_limit {
+            units.push(hunk_piece(&piece, &mut header));
+            piece.clear();
+            piece_size = 0;
+        }
+        if size > line_limit {
+            let header_line = header.as_mut().map(|h| h.take(&[line]));
+            for (i, part) in split_long_line(line, line_limit, budget).into_iter().enumerate() {
+                units.push(match (&header_line, i) {
+                    (Some(h), 0) => format!("{}\n{}", h, part),
+                    _ => part,
+                });
+            }
+            continue;
+        }
+        piece.push(line);
+        piece_size += size;
+    }
+    if !piece.is_empty() {
+        units.push(hunk_piece(&piece, &mut header));
+    }
+}
+
+/// ハンクの一部に `@@` 行を付ける
+fn hunk_piece(lines: &[&str], header: &mut Option<HunkHeader>) -> String {
+    let body = lines.join("\n");
+    match header {
+        Some(h) => format!("{}\n{}", h.take(lines), body),
+        None => body,
+    }
+}
+
+/// `@@ -a,b +c,d @@ 文脈` の行番号（次に続く行の番号で持つ）
+struct HunkHeader {
+    old_line: usize,
+    new_line: usize,
+    context: String,
+}
+
+impl HunkHeader {
+    fn parse(line: &str) -> Option<HunkHeader> {
+        let (ranges, context) = line.strip_prefix("@@ -")?.split_once(" @@")?;
+        let (old, new) = ranges.split_once(" +")?;
+        let next_line = |range: &str| -> Option<usize> {
+            let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+            let (start, count): (usize, usize) = (start.parse().ok()?, count.parse().ok()?);
+            // 行数が 0 のときの開始行は、その直前の行を指す
+            Some(if count == 0 { start + 1 } else { start })
+        };
+        Some(HunkHeader {
+            old_line: next_line(old)?,
+            new_line: next_line(new)?,
+            context: context.to_string(),
+        })
+    }
+
+    /// 大きさの見積もり用の `@@` 行
+    fn placeholder(&self) -> String {
+        format!("@@ -999999,999999 +999999,999999 @@{}", self.context)
+    }
+
+    /// `lines` の分の `@@` 行を作り、次の部分の行番号に進める
+    fn take(&mut self, lines: &[&str]) -> String {
+        let old_count = lines.iter().filter(|l| l.starts_with([' ', '-'])).count();
+        let new_count = lines.iter().filter(|l| l.starts_with([' ', '+'])).count();
+        let range = |line: usize, count: usize| match count {
+            0 => format!("{},0", line.saturating_sub(1)),
+            _ => format!("{},{}", line, count),
+        };
+        let header = format!(
+            "@@ -{} +{} @@{}",
+            range(self.old_line, old_count),
+            range(self.new_line, new_count),
+            self.context
+        );
+        self.old_line += old_count;
+        self.new_line += new_count;
+        header
+    }
+}
+
+/// JSON 文字列としてエスケープされた後の大きさを測る（前後の `"` は含めない）
+fn measure_content(text: &str, budget: &ChunkBudget) -> usize {
+    match serde_json::to_string(text) {
+        Ok(s) => budget.measure(&s[1..s.len() - 1]),
+        Err(_) => budget.measure(text),
+    }
+}
+
+/// 1 行を文字境界で `limit` 以内に分ける
+///
+/// 同じ理由で数十文字ずつのブロックで測って詰める（継ぎ目の分だけトークン数は多めに見積もられる）。
+fn split_long_line(line: &str, limit: usize, budget: &ChunkBudget) -> Vec<String> {
+    const BLOCK_CHARS: usize = 64;
+
+    let mut units = Vec::new();
+    for block in char_blocks(line, BLOCK_CHARS) {
+        let size = measure_content(block, budget);
+        if size > limit {
+            // ブロック単体でも収まらないほど小さい上限なら 1 文字ずつ
+            units.extend(char_blocks(block, 1).map(|c| (c, measure_content(c, budget))));
+        } else {
+            units.push((block, size));
+        }
+    }
+
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut current_size = 0;
+    for (unit, size) in units {
+        if !current.is_empty() && current_size + size > limit {
+            parts.push(std::mem::take(&mut current));
+            current_size = 0;
+        }
+        current.push_str(unit);
+        current_size += size;
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// `n` 文字ずつに区切った部分文字列
+fn char_blocks(text: &str, n: usize) -> impl Iterator<Item = &str> {
+    let mut bounds: Vec<usize> = text.char_indices().map(|(i, _)| i).step_by(n).collect();
+    bounds.push(text.len());
+    (0..bounds.len() - 1).map(move |i| &text[bounds[i]..bounds[i + 1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+
+    use super::*;
+    use crate::export::labelled_chunks;
+    use crate::tree::{FileStatus, TreeNode};
+
+    fn file(name: &str, content: &str) -> FileInfo {
+        FileInfo {
+            file_url: format!("/proj/{}", name),
+            file_name: name.to_string(),
+            file_content: content.to_string(),
+            ..FileInfo::default()
+        }
+    }
+
+    fn diff(name: &str, lines: usize) -> FileDiff {
+        FileDiff {
+            file_name: name.to_string(),
+            status: "modified".to_string(),
+            diff: (0..lines).map(|i| format!("+changed line {}\n", i)).collect(),
+        }
+    }
+
+    /// `n` 個のファイルを直下に持つツリー
+    fn tree(n: usize) -> TreeNode {
+        let mut root = TreeNode::dir("proj", "");
+        for i in 0..n {
+            let name = format!("file_{}.rs", i);
+            root.children.push(TreeNode::file(&name, &name, 100, FileStatus::NotMatched));
+        }
+        root.finish();
+        root
+    }
+
+    fn output(files: Vec<FileInfo>, diffs: Vec<FileDiff>, tree: TreeNode) -> ProjectOutput {
+        ProjectOutput {
+            llm_note: Some("補足".to_string()),
+            files,
+            tree_view: Some(tree.render_text()),
+            tree: Some(tree),
+            diffs,
+            warnings: Vec::new(),
+            compaction: None,
+            redactions: Vec::new(),
+        }
+    }
+
+    fn bytes(limit: usize) -> ChunkBudget {
+        ChunkBudget {
+            limit,
+            unit: ChunkUnit::Bytes,
+            encoding: TokenEncoding::default(),
+        }
+    }
+
+    fn sample() -> ProjectOutput {
+        let long_file: String = (0..80).map(|i| format!("fn line_{}() {{ \"値\" }}\n", i)).collect();
+        let minified = "var x=\"あいう\\\"\";".repeat(120);
+        output(
+            vec![file("a.rs", "fn a() {}\n"), file("src/long.rs", &long_file), file("min.js", &minified)],
+            vec![diff("a.rs", 3), diff("src/long.rs", 20)],
+            tree(2),
+        )
+    }
+
+    #[test]
+    fn every_rendered_chunk_fits_the_limit() {
+        let budget = bytes(900);
+        let ctx = RenderContext::default();
+        let texts =
+            labelled_chunks(Some("/proj"), "LLM への補足", &sample(), &budget, OutputFormat::Json, &ctx, &Local::now())
+                .unwrap();
+        assert!(texts.len() > 1);
+        for text in &texts {
+            assert!(text.len() <= budget.limit, "{} > {}:\n{}", text.len(), budget.limit, text);
+        }
+    }
+
+    /// `hunks` 個のハンク（それぞれ 3 行の文脈と `changes` 行の変更）を持つ差分
+    fn hunk_diff(name: &str, hunks: usize, changes: usize) -> FileDiff {
+        let mut diff = format!("--- a/{0}\n+++ b/{0}", name);
+        for h in 0..hunks {
+            let start = h * 100 + 1;
+            diff.push_str(&format!("\n@@ -{},{} +{},{} @@ fn f{}()", start, changes + 3, start, changes + 3, h));
+            for i in 0..3 {
+                diff.push_str(&format!("\n context {}", i));
+            }
+            for i in 0..changes {
+                diff.push_str(&format!("\n-old {}\n+new {}", i, i));
+            }
+        }
+        FileDiff {
+            file_name: name.to_string(),
+            status: "modified".to_string(),
+            diff,
+        }
+    }
+
+    #[test]
+    fn every_format_fits_the_limit() {
+        let mut out = sample();
+        out.diffs.push(hunk_diff("src/big.rs", 4, 30));
+        let budget = bytes(1200);
+        let ctx = RenderContext::default();
+        for format in OutputFormat::ALL {
+            let texts = labelled_chunks(Some("/proj"), "補足", &out, &budget, format, &ctx, &Local::now()).unwrap();
+            assert!(texts.len() > 1);
+            for text in &texts {
+                assert!(text.len() <= budget.limit, "{:?}: {} > {}:\n{}", format, text.len(), budget.limit, text);
+            }
+        }
+    }
+
+    #[test]
+    fn template_larger_than_the_limit_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("text-read-chunk-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(crate::render::TEMPLATE_FILE), "x".repeat(2000)).unwrap();
+        let dir_str = dir.to_string_lossy().to_string();
+        let ctx = RenderContext::new(Some(&dir_str), "", &Local::now());
+        let result =
+            labelled_chunks(Some(&dir_str), "", &sample(), &bytes(1000), OutputFormat::Template, &ctx, &Local::now());
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn large_diff_is_split_on_hunks_with_its_header() {
+        let big = hunk_diff("src/big.rs", 6, 4);
+        let out = output(Vec::new(), vec![big.clone()], tree(0));
+        let budget = bytes(700);
+        let chunks = chunk_output(&out, &budget).unwrap();
+        let pieces: Vec<&FileDiff> = chunks.iter().flat_map(|c| &c.output.diffs).collect();
+        assert!(pieces.len() > 1);
+        for piece in &pieces {
+            assert!(piece.diff.starts_with("--- a/src/big.rs\n+++ b/src/big.rs\n@@ -"), "{}", piece.diff);
+        }
+        for chunk in &chunks {
+            assert!(chunk.label().len() + chunk.to_json().unwrap().len() <= budget.limit);
+        }
+        // 見出し行を除いて繋げると元のハンクに戻る
+        let hunks: Vec<&str> = pieces.iter().map(|p| p.diff.splitn(3, '\n').nth(2).unwrap()).collect();
+        assert_eq!(hunks.join("\n"), big.diff.splitn(3, '\n').nth(2).unwrap());
+    }
+
+    #[test]
+    fn large_hunk_is_split_with_renumbered_headers() {
+        let big = hunk_diff("a.rs", 1, 40);
+        let out = output(Vec::new(), vec![big], tree(0));
+        let chunks = chunk_output(&out, &bytes(600)).unwrap();
+        let pieces: Vec<&FileDiff> = chunks.iter().flat_map(|c| &c.output.diffs).collect();
+        assert!(pieces.len() > 2);
+        // 各部分の `@@` 行は、その部分の行数と続きの行番号を表す
+        let (mut old_line, mut new_line) = (1, 1);
+        for piece in &pieces {
+            for hunk in piece.diff.split("\n@@ ").skip(1) {
+                let mut lines = hunk.lines();
+                let header = HunkHeader::parse(&format!("@@ {}", lines.next().unwrap())).unwrap();
+                assert_eq!((header.old_line, header.new_line), (old_line, new_line), "{}", piece.diff);
+                let body: Vec<&str> = lines.collect();
+                old_line += body.iter().filter(|l| !l.starts_with('+')).count();
+                new_line += body.iter().filter(|l| !l.starts_with('-')).count();
+            }
+        }
+        assert_eq!((old_line, new_line), (44, 44));
+    }
+
+    #[test]
+    fn hunk_header_is_advanced_by_each_piece() {
+        let mut header = HunkHeader::parse("@@ -10,4 +10,5 @@ fn x()").unwrap();
+        assert_eq!(header.take(&[" a", "-b"]), "@@ -10,2 +10,1 @@ fn x()");
+        assert_eq!(header.take(&["+c", "+d", " e"]), "@@ -12,1 +11,3 @@ fn x()");
+        let mut added = HunkHeader::parse("@@ -0,0 +1,3 @@").unwrap();
+        assert_eq!(added.take(&["+a"]), "@@ -0,0 +1,1 @@");
+        assert_eq!(added.take(&["+b", "+c"]), "@@ -0,0 +2,2 @@");
+        assert!(HunkHeader::parse("+not a header").is_none());
+    }
+
+    #[test]
+    fn every_chunk_json_fits_the_limit_in_tokens() {
+        let budget = ChunkBudget {
+            limit: 300,
+            unit: ChunkUnit::Tokens,
+            encoding: TokenEncoding::Cl100k,
+        };
+        for chunk in chunk_output(&sample(), &budget).unwrap() {
+            let text = format!("{}{}", chunk.label(), chunk.to_json().unwrap());
+            assert!(budget.measure(&text) <= budget.limit);
+        }
+    }
+
+    #[test]
+    fn rejoined_chunks_reproduce_the_content() {
+        let original = sample();
+        let chunks = chunk_output(&original, &bytes(700)).unwrap();
+
+        for f in &original.files {
+            let pieces: Vec<&FileInfo> = chunks
+                .iter()
+                .flat_map(|c| &c.output.files)
+                .filter(|p| p.file_name == f.file_name)
+                .collect();
+            let joined: String = pieces.iter().map(|p| p.file_content.as_str()).collect();
+            assert_eq!(joined, f.file_content, "{}", f.file_name);
+            if pieces.len() > 1 {
+                // 行範囲は 1 行目から途切れずに続く
+                assert_eq!(pieces[0].line_range.unwrap()[0], 1);
+                for w in pieces.windows(2) {
+                    let (a, b) = (w[0].line_range.unwrap(), w[1].line_range.unwrap());
+                    assert!(b[0] == a[1] || b[0] == a[1] + 1);
+                }
+            }
+        }
+
+        let mut diffs: Vec<&str> = chunks.iter().flat_map(|c| &c.output.diffs).map(|d| d.file_name.as_str()).collect();
+        diffs.dedup();
+        assert_eq!(diffs, ["a.rs", "src/long.rs"]);
+        assert!(chunks.iter().all(|c| c.header.total_parts == chunks.len()));
+        assert!(chunks[1..].iter().all(|c| c.output.tree.is_none() && c.output.llm_note.is_none()));
+    }
+
+    #[test]
+    fn large_tree_gets_its_own_chunk() {
+        let out = output(vec![file("a.rs", &"a".repeat(400))], Vec::new(), tree(3));
+        let budget = bytes(1000);
+        let chunks = chunk_output(&out, &budget).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].output.files.is_empty());
+        assert!(chunks[0].output.tree.is_some());
+        assert_eq!(chunks[1].output.files[0].file_content, "a".repeat(400));
+        for chunk in &chunks {
+            assert!(chunk.label().len() + chunk.to_json().unwrap().len() <= budget.limit);
+        }
+    }
+
+    #[test]
+    fn small_output_is_one_chunk_without_line_range() {
+        let out = output(vec![file("a.rs", "fn a() {}\n")], Vec::new(), tree(1));
+        let chunks = chunk_output(&out, &bytes(50_000)).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].output.files[0].line_range, None);
+        assert_eq!(chunks[0].header.files, ["a.rs"]);
+    }
+
+    #[test]
+    fn zero_limit_is_an_error() {
+        assert!(chunk_output(&output(Vec::new(), Vec::new(), tree(0)), &bytes(0)).is_err());
+    }
+}
@@ -0,0 +1,399 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use gpt_text_read::backup::backup_included_files;
+use gpt_text_read::chunk::ChunkBudget;
+use gpt_text_read::export::{chunk_path, default_file_name, labelled_chunks, labelled_text};
+use gpt_text_read::git::{DiffContent, DiffSpec, GitSelection};
+use gpt_text_read::models::{LoadedSettings, ProjectOutput};
+use gpt_text_read::render::{render_with, OutputFormat, RenderContext};
+use gpt_text_read::settings::load_settings;
+use gpt_text_read::snapdiff::{compare_snapshots, resolve_snapshot, SnapshotSource};
+use gpt_text_read::tokens::{token_report, TokenReport};
+use gpt_text_read::Collector;
+
+const USAGE: &str = "\
+使い方: gpt-text-read <サブコマンド> [オプション]
+  (サブコマンドなしで起動すると GUI が立ち上がります)
+
+サブコマンド:
+  collect    ファイルを収集し ProjectOutput を出力（既定: JSON, ツリーなし）
+  tree       ディレクトリツリーのテキストを出力
+  export     GUI の「テキスト出力」と同じラベル付きテキストを出力
+  snapshot   収集したファイルを target/backup/ にスナップショット
+  snapdiff   2 つのスナップショット（または現在のファイル）の追加・削除・変更と差分を出力（-f json で JSON）
+  tokens     出力全体とファイルごとのトークン数を表示（TOKEN_BUDGET 超過で終了コード 1）
+  help       この説明を表示
+
+オプション:
+  -d, --dir <DIR>          プロジェクトフォルダ（既定: カレントディレクトリ）
+  -o, --output <FILE>      出力先ファイル（既定: 標準出力。export は OUTPUT_PATH も参照）
+      --tree / --no-tree   collect/export/tokens にツリーを含めるか（collect 既定: なし, 他は既定: あり）
+  -f, --format <FORMAT>    出力形式 json / markdown / xml / template（collect 既定: json, 他は OUTPUT_FORMAT）
+                           template はプロジェクトフォルダの text-read-template.hbs を使う
+  -c, --comment <TEXT>     snapshot のフォルダに付加する英数字コメント
+      --from <SNAP>        snapdiff の比較元（既定: latest。スナップショットのフォルダ名か
+                           その先頭部分、latest / previous / live）
+      --to <SNAP>          snapdiff の比較先（既定: live＝現在のファイル）
+  -n, --line-numbers       ファイル内容に行番号を付ける（LINE_NUMBERS=true と同じ）
+      --allow-denied       設定ファイルの DENY_OVERRIDE: を適用する（指定しなければ拒否リストを上書きしない）
+      --git-changed <SEL>  include パターンの代わりに git の変更から選ぶ（GIT_CHANGED= と同じ。
+                           working-tree / since:<ref> / merge-base:<ref> / off）
+      --diff <SPEC>        git の差分も出力する（DIFF= と同じ。HEAD / <ref> は作業ツリーとの差分、
+                           <ref1>..<ref2> は 2 つの ref の間、<ref1>...<ref2> は merge-base から、
+                           off は出さない）
+      --diff-content <M>   差分を出すときのファイル内容（DIFF_CONTENT= と同じ。all / changed / none）
+      --chunk              export をファイル境界でチャンク分割（CHUNK_LIMIT / CHUNK_UNIT に従う。
+                           出力先 <name>.txt に対し <name>_chunk_1.txt, ... を書く）
+";
+
+/// コマンドライン引数の解析結果
+struct CliArgs {
+    command: String,
+    dir: String,
+    output: Option<String>,
+    tree: Option<bool>,
+    comment: String,
+    chunk: bool,
+    format: Option<OutputFormat>,
+    line_numbers: bool,
+    allow_denied: bool,
+    git_selection: Option<GitSelection>,
+    // Some(None) は --diff off
+    diff: Option<Option<DiffSpec>>,
+    diff_content: Option<DiffContent>,
+    snap_from: Option<String>,
+    snap_to: Option<String>,
+}
+
+/// コマンドラインモードで実行し、終了コードを返す
+pub fn run_cli(args: &[String]) -> i32 {
+    let parsed = match parse_args(args) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    let result = match parsed.command.as_str() {
+        "collect" => cmd_collect(&parsed),
+        "tree" => cmd_tree(&parsed),
+        "export" => cmd_export(&parsed),
+        "snapshot" => cmd_snapshot(&parsed),
+        "snapdiff" => cmd_snapdiff(&parsed),
+        "tokens" => cmd_tokens(&parsed),
+        "help" | "-h" | "--help" => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("不明なサブコマンドです: {}\n\n{}", other, USAGE)),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("エラー: {}", e);
+            1
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut iter = args.iter();
+    let command = iter
+        .next()
+        .cloned()
+        .ok_or_else(|| "サブコマンドが指定されていません。".to_string())?;
+
+    let mut parsed = CliArgs {
+        command,
+        dir: ".".to_string(),
+        output: None,
+        tree: None,
+        comment: String::new(),
+        chunk: false,
+        format: None,
+        line_numbers: false,
+        allow_denied: false,
+        git_selection: None,
+        diff: None,
+        diff_content: None,
+        snap_from: None,
+        snap_to: None,
+    };
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-d" | "--dir" => parsed.dir = option_value(arg, iter.next())?,
+            "-o" | "--output" => parsed.output = Some(option_value(arg, iter.next())?),
+            "-c" | "--comment" => parsed.comment = option_value(arg, iter.next())?,
+            "-f" | "--format" => {
+                let name = option_value(arg, iter.next())?;
+                let format = OutputFormat::from_name(&name).ok_or_else(|| {
+                    let names: Vec<&str> = OutputFormat::ALL.iter().map(|f| f.name()).collect();
+                    format!("不明な出力形式です: {} ({} のいずれか)", name, names.join(" / "))
+                })?;
+                parsed.format = Some(format);
+            }
+            "--tree" => parsed.tree = Some(true),
+            "--no-tree" => parsed.tree = Some(false),
+            "--chunk" => parsed.chunk = true,
+            "-n" | "--line-numbers" => parsed.line_numbers = true,
+            "--allow-denied" => parsed.allow_denied = true,
+            "--git-changed" => {
+                let value = option_value(arg, iter.next())?;
+                let selection = GitSelection::from_setting(&value).ok_or_else(|| {
+                    format!(
+                        "不明な選択です: {} (working-tree / since:<ref> / merge-base:<ref> / off のいずれか)",
+                        value
+                    )
+                })?;
+                parsed.git_selection = Some(selection);
+            }
+            "--from" => parsed.snap_from = Some(option_value(arg, iter.next())?),
+            "--to" => parsed.snap_to = Some(option_value(arg, iter.next())?),
+            "--diff" => parsed.diff = Some(DiffSpec::from_setting(&option_value(arg, iter.next())?)),
+            "--diff-content" => {
+                let name = option_value(arg, iter.next())?;
+                let content = DiffContent::from_name(&name)
+                    .ok_or_else(|| format!("不明な指定です: {} (all / changed / none のいずれか)", name))?;
+                parsed.diff_content = Some(content);
+            }
+            other => return Err(format!("不明なオプションです: {}", other)),
+        }
+    }
+
+    if !Path::new(&parsed.dir).is_dir() {
+        return Err(format!("プロジェクトフォルダが見つかりません: {}", parsed.dir));
+    }
+
+    Ok(parsed)
+}
+
+fn option_value(name: &str, value: Option<&String>) -> Result<String, String> {
+    value
+        .cloned()
+        .ok_or_else(|| format!("{} には値が必要です。", name))
+}
+
+/// 標準出力またはファイルへ書き出す
+fn write_output(path: Option<&Path>, text: &str) -> Result<(), String> {
+    match path {
+        Some(p) => {
+            if let Some(parent) = p.parent() {
+                if !parent.as_os_str().is_empty() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("フォルダ作成失敗: {} ({})", e, parent.display()))?;
+                }
+            }
+            fs::write(p, text).map_err(|e| format!("書き込み失敗: {} ({})", e, p.display()))?;
+            eprintln!("出力完了: {}", p.display());
+            Ok(())
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            writeln!(stdout, "{}", text).map_err(|e| format!("標準出力への書き込み失敗: {}", e))
+        }
+    }
+}
+
+/// 設定ファイルを読み込み、不正なパターンがあれば実行を拒否する
+///
+/// `DENY_OVERRIDE:` は `--allow-denied` を指定したときだけ適用する。
+/// `--git-changed`・`--diff`・`--diff-content` は設定ファイルの値より優先する。
+fn load_checked_settings(args: &CliArgs) -> Result<LoadedSettings, String> {
+    let mut settings = load_settings(&args.dir);
+    if !settings.pattern_errors.is_empty() {
+        for e in &settings.pattern_errors {
+            eprintln!("text-read-settings.txt: {}", e);
+        }
+        return Err(format!(
+            "設定ファイルに不正なパターンが {} 件あります。修正してから再実行してください。",
+            settings.pattern_errors.len()
+        ));
+    }
+    if !settings.options.deny_overrides.is_empty() {
+        let overrides = settings.options.deny_overrides.join(", ");
+        if args.allow_denied {
+            eprintln!("拒否リストを上書きします: {}", overrides);
+        } else {
+            eprintln!("警告: DENY_OVERRIDE ({}) は --allow-denied の指定がないため無視します", overrides);
+        }
+    }
+    settings.options.allow_denied = args.allow_denied;
+    if let Some(selection) = &args.git_selection {
+        settings.options.git_selection = selection.clone();
+    }
+    if let Some(diff) = &args.diff {
+        settings.options.diff = diff.clone();
+    }
+    if let Some(content) = args.diff_content {
+        settings.options.diff_content = content;
+    }
+    if !settings.options.git_selection.is_off() {
+        eprintln!("git の変更から選択します: {}", settings.options.git_selection.to_setting());
+    }
+    Ok(settings)
+}
+
+/// スキップ等の警告と圧縮・差分・伏せ字の結果を標準エラーへ表示
+fn report_warnings(output: &ProjectOutput) {
+    for w in &output.warnings {
+        eprintln!("警告: {} ({})", w.file_name, w.reason);
+    }
+    if let Some(report) = &output.compaction {
+        eprintln!("圧縮: {}", report.summary());
+    }
+    if !output.diffs.is_empty() {
+        eprintln!("差分: {} ファイル", output.diffs.len());
+    }
+    for r in &output.redactions {
+        eprintln!("伏せ字: {} {}行目 ({})", r.file_name, r.line, r.kind);
+    }
+}
+
+/// TOKEN_BUDGET が設定されていれば数えて、超過時に警告する
+fn warn_token_budget(settings: &LoadedSettings, output: &ProjectOutput, rendered: &str) {
+    if let Some(budget) = settings.options.token_budget {
+        let report = token_report(output, rendered, settings.options.token_encoding, Some(budget));
+        if report.over_budget() {
+            eprintln!("警告: トークン予算を超えています ({})", report.summary());
+        }
+    }
+}
+
+/// テンプレート形式用の情報（プロジェクト・日時・開発メモ）
+fn render_context(args: &CliArgs, settings: &LoadedSettings, now: &DateTime<Local>) -> RenderContext {
+    RenderContext::new(Some(&args.dir), &settings.dev_memo.join("\n"), now)
+}
+
+fn cmd_collect(args: &CliArgs) -> Result<(), String> {
+    let settings = load_checked_settings(args)?;
+    let output = Collector::with_settings(args.dir.as_str(), settings.clone())
+        .tree(args.tree.unwrap_or(false))
+        .line_numbers(args.line_numbers || settings.options.line_numbers)
+        .collect()?;
+    report_warnings(&output);
+    let ctx = render_context(args, &settings, &Local::now());
+    let rendered = render_with(&output, args.format.unwrap_or_default(), &ctx)?;
+    warn_token_budget(&settings, &output, &rendered);
+    write_output(args.output.as_deref().map(Path::new), &rendered)
+}
+
+fn cmd_tree(args: &CliArgs) -> Result<(), String> {
+    let settings = load_checked_settings(args)?;
+    let tree = Collector::with_settings(args.dir.as_str(), settings).tree_view();
+    write_output(args.output.as_deref().map(Path::new), &tree)
+}
+
+fn cmd_export(args: &CliArgs) -> Result<(), String> {
+    let settings = load_checked_settings(args)?;
+    let output = Collector::with_settings(args.dir.as_str(), settings.clone())
+        .tree(args.tree.unwrap_or(true))
+        .line_numbers(args.line_numbers || settings.options.line_numbers)
+        .collect()?;
+    report_warnings(&output);
+    let format = args.format.unwrap_or(settings.options.output_format);
+    let now = Local::now();
+    let ctx = render_context(args, &settings, &now);
+    let rendered = render_with(&output, format, &ctx)?;
+
+    // テンプレートは見出しも含めて出力するのでラベルを付けない
+    let llm_raw = settings.llm_note.join("\n");
+    let labelled_val = if format.is_self_labelled() {
+        rendered
+    } else {
+        labelled_text(Some(&args.dir), &llm_raw, &rendered, &now)
+    };
+    warn_token_budget(&settings, &output, &labelled_val);
+
+    // -o 未指定で OUTPUT_PATH があればそこへ、どちらもなければ標準出力
+    let out_path: Option<PathBuf> = match (&args.output, &settings.output_path) {
+        (Some(o), _) => Some(PathBuf::from(o)),
+        (None, Some(dir)) => Some(Path::new(dir).join(default_file_name(Some(&args.dir), &now))),
+        (None, None) => None,
+    };
+
+    if !args.chunk {
+        return write_output(out_path.as_deref(), &labelled_val);
+    }
+    let out_path = out_path.ok_or_else(|| {
+        "--chunk には出力先（-o または OUTPUT_PATH）が必要です。".to_string()
+    })?;
+    let budget = ChunkBudget::from_options(&settings.options);
+    let chunks = labelled_chunks(Some(&args.dir), &llm_raw, &output, &budget, format, &ctx, &now)?;
+    for (i, text) in chunks.iter().enumerate() {
+        write_output(Some(&chunk_path(&out_path, i + 1)), text)?;
+    }
+    Ok(())
+}
+
+fn cmd_snapshot(args: &CliArgs) -> Result<(), String> {
+    if !args.comment.is_empty() && !args.comment.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("コメントは英数字のみ指定できます。".to_string());
+    }
+    let settings = load_checked_settings(args)?;
+    let output = Collector::with_settings(args.dir.as_str(), settings).tree(false).collect()?;
+    report_warnings(&output);
+    let dest = backup_included_files(&args.dir, &output, &args.comment)?;
+    println!("スナップショット作成完了: {}", dest.display());
+    Ok(())
+}
+
+fn cmd_snapdiff(args: &CliArgs) -> Result<(), String> {
+    let old = resolve_snapshot(&args.dir, args.snap_from.as_deref().unwrap_or("latest"))?;
+    let new = resolve_snapshot(&args.dir, args.snap_to.as_deref().unwrap_or("live"))?;
+    let settings = load_checked_settings(args)?;
+    // 現在のファイル側は include・exclude で選んだファイル（上限等で外す前）
+    let live = if old == SnapshotSource::Live || new == SnapshotSource::Live {
+        Collector::with_settings(args.dir.as_str(), settings.clone()).selected_files()?
+    } else {
+        Vec::new()
+    };
+    let diff = compare_snapshots(&args.dir, &old, &new, &live, &settings.options)?;
+    eprintln!("{} → {}: {}", diff.old, diff.new, diff.summary());
+    let text = match args.format {
+        Some(OutputFormat::Json) => {
+            serde_json::to_string_pretty(&diff).map_err(|e| format!("JSON変換に失敗: {}", e))?
+        }
+        _ => diff.to_text(),
+    };
+    write_output(args.output.as_deref().map(Path::new), &text)
+}
+
+fn cmd_tokens(args: &CliArgs) -> Result<(), String> {
+    let settings = load_checked_settings(args)?;
+    let output = Collector::with_settings(args.dir.as_str(), settings.clone())
+        .tree(args.tree.unwrap_or(true))
+        .line_numbers(args.line_numbers || settings.options.line_numbers)
+        .collect()?;
+    report_warnings(&output);
+    let ctx = render_context(args, &settings, &Local::now());
+    let rendered = render_with(&output, args.format.unwrap_or(settings.options.output_format), &ctx)?;
+
+    let report = token_report(
+        &output,
+        &rendered,
+        settings.options.token_encoding,
+        settings.options.token_budget,
+    );
+    write_output(args.output.as_deref().map(Path::new), &format_token_report(&report))?;
+
+    if report.over_budget() {
+        return Err(format!("トークン予算を超えています ({})", report.summary()));
+    }
+    Ok(())
+}
+
+/// トークン数の一覧表
+fn format_token_report(report: &TokenReport) -> String {
+    let mut lines = vec![format!("合計: {}", report.summary())];
+    for (name, count) in &report.per_file {
+        lines.push(format!("{:>10}  {}", count, name));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::compact::{compact_lines, CompactModes, CompactionReport};
+use crate::fileops::{
+    build_tree, check_compact_rule, check_encoding_override, check_pattern, check_patterns, check_regex,
+    collect_target_files,
+};
+use crate::deny::deny_matcher;
+use crate::git::{added_file_diff, changed_files, file_diffs, untracked_files, DiffContent, DiffSpec, GitSelection};
+use crate::lang::language_for_path;
+use crate::limits::{apply_limits, SizeLimits};
+use crate::linenum::number_lines;
+use crate::metadata::MetadataFields;
+use crate::models::{CollectOptions, FileDiff, FileInfo, FileWarning, LoadedSettings, PatternError, PatternKind, ProjectOutput};
+use crate::order::sort_files;
+use crate::outline::outline_lines;
+use crate::redact::{Redaction, Redactor};
+use crate::tokens::count_tokens;
+use crate::patterns::{PathMatcher, GLOB_PREFIX};
+use crate::settings::load_settings;
+use crate::tree::{FileStatus, TreeNode};
+
+/// ファイル収集のビルダー
+///
+/// ベースフォルダと include/exclude パターン（正規表現、または `GLOB:` 付きのグロブ）を指定して
+/// `collect` を呼ぶと、GUI/CLI と同じ `ProjectOutput` を返す。
+///
+/// ```no_run
+/// use gpt_text_read::Collector;
+///
+/// let output = Collector::new("path/to/project")
+///     .include(["^src/.*\\.rs$"])
+///     .exclude(["^target/"])
+///     .tree(true)
+///     .collect()
+///     .unwrap();
+/// println!("{} files", output.files.len());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Collector {
+    base_dir: String,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    tree: bool,
+    llm_note: Option<String>,
+    options: CollectOptions,
+}
+
+impl Collector {
+    /// パターン未指定の Collector を作る
+    pub fn new(base_dir: impl Into<String>) -> Self {
+        Collector {
+            base_dir: base_dir.into(),
+            ..Default::default()
+        }
+    }
+
+    /// `text-read-settings.txt` の include/exclude パターンと LLM補足を読み込んだ Collector を作る
+    ///
+    /// ツリー表示は GUI の既定と同じく有効になる。
+    pub fn from_settings(base_dir: impl Into<String>) -> Self {
+        let base_dir = base_dir.into();
+        let settings = load_settings(&base_dir);
+        Self::with_settings(base_dir, settings)
+    }
+
+    /// 読み込み済みの設定から Collector を作る
+    pub fn with_settings(base_dir: impl Into<String>, settings: LoadedSettings) -> Self {
+        Collector {
+            base_dir: base_dir.into(),
+            include: settings.patterns_include,
+            exclude: settings.patterns_exclude,
+            tree: true,
+            llm_note: Some(settings.llm_note.join("\n")),
+            options: settings.options,
+        }
+    }
+
+    /// include パターンを追加
+    pub fn include<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// exclude パターンを追加
+    pub fn exclude<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// ツリー表示を生成するかどうか
+    pub fn tree(mut self, on: bool) -> Self {
+        self.tree = on;
+        self
+    }
+
+    /// 出力の先頭に付ける LLM補足
+    pub fn llm_note(mut self, note: Option<String>) -> Self {
+        self.llm_note = note;
+        self
+    }
+
+    /// 収集オプション
+    pub fn options(mut self, options: CollectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// ignore ファイル（.gitignore / .textreadignore 等）に従うかどうか
+    pub fn respect_ignore(mut self, on: bool) -> Self {
+        self.options.respect_ignore = on;
+        self
+    }
+
+    /// file_content に行番号を付けるかどうか
+    pub fn line_numbers(mut self, on: bool) -> Self {
+        self.options.line_numbers = on;
+        self
+    }
+
+    /// 設定ファイルの `DENY_OVERRIDE:` を適用するかどうか（既定: しない）
+    pub fn allow_denied(mut self, on: bool) -> Self {
+        self.options.allow_denied = on;
+        self
+    }
+
+    /// include パターンの代わりに git の変更からファイルを選ぶ（`GitSelection::Off` で include パターンに戻す）
+    pub fn git_selection(mut self, selection: GitSelection) -> Self {
+        self.options.git_selection = selection;
+        self
+    }
+
+    /// ベースフォルダ
+    pub fn base_dir(&self) -> &str {
+        &self.base_dir
+    }
+
+    /// コンパイルできないパターンや不明なエンコーディング名を返す
+    ///
+    /// 行番号は `include` / `exclude` に渡したリスト（空行を含む）の中での位置。
+    pub fn validate(&self) -> Vec<PatternError> {
+        let mut errors = check_patterns(PatternKind::Include, self.include.iter().map(|s| s.as_str()));
+        errors.extend(check_patterns(PatternKind::Exclude, self.exclude.iter().map(|s| s.as_str())));
+        for (i, ov) in self.options.encoding_overrides.iter().enumerate() {
+            errors.extend(check_encoding_override(i + 1, &ov.encoding, &ov.pattern));
+        }
+        for (i, rule) in self.options.compact_rules.iter().enumerate() {
+            errors.extend(check_compact_rule(i + 1, &rule.modes, &rule.pattern));
+        }
+        for (i, pat) in self.options.outline_patterns.iter().enumerate() {
+            errors.extend(check_regex(PatternKind::Outline, i + 1, pat));
+        }
+        for (i, pat) in self.options.redact_patterns.iter().enumerate() {
+            errors.extend(check_regex(PatternKind::Redact, i + 1, pat));
+        }
+        let deny_globs = self.options.deny_patterns.iter().chain(&self.options.deny_overrides);
+        for (i, glob) in deny_globs.enumerate() {
+            errors.extend(check_pattern(PatternKind::Deny, i + 1, &format!("{}{}", GLOB_PREFIX, glob)));
+        }
+        for (i, glob) in self.options.pinned.iter().enumerate() {
+            errors.extend(check_pattern(PatternKind::Pin, i + 1, &format!("{}{}", GLOB_PREFIX, glob)));
+        }
+        errors
+    }
+
+    /// ファイルを収集して `ProjectOutput` を返す
+    ///
+    /// 不正なパターンがあると、黙って無視せずにエラーを返す。
+    pub fn collect(&self) -> Result<ProjectOutput, String> {
+        self.check_valid()?;
+
+        let inc_patterns = self.include_matcher()?;
+        let exc_patterns = PathMatcher::compile(self.exclude.iter().map(|s| s.as_str()));
+
+        let (mut files, mut warnings) =
+            collect_target_files(&self.base_dir, &inc_patterns, &exc_patterns, &self.options)?;
+        // 合計サイズの上限は先頭から詰めるので、先に並べ替える
+        sort_files(&mut files, self.options.order, &self.include, &self.options.pinned);
+        let mut diffs = match &self.options.diff {
+            Some(spec) => {
+                let diffs = self.collect_diffs(spec, &inc_patterns, &exc_patterns, &files)?;
+                match self.options.diff_content {
+                    DiffContent::All => {}
+                    DiffContent::Changed => files.retain(|f| diffs.iter().any(|d| d.file_name == f.file_name)),
+                    DiffContent::None => files.clear(),
+                }
+                diffs
+            }
+            None => Vec::new(),
+        };
+        let redactions = redact_contents(&mut files, &mut diffs, &self.options);
+        let compaction = transform_contents(&mut files, &self.options, &mut warnings);
+        apply_limits(&mut files, &self.options.limits, self.options.token_encoding, &mut warnings);
+        let tree = if self.tree {
+            // 警告付きで除外したファイルより、出力に含めたファイルを優先する
+            let mut status: HashMap<&str, FileStatus> = HashMap::new();
+            for w in &warnings {
+                status.insert(&w.file_name, FileStatus::Skipped(w.reason.clone()));
+            }
+            for f in &files {
+                status.insert(&f.file_name, FileStatus::Included(f.tokens));
+            }
+            Some(build_tree(&self.base_dir, &exc_patterns, &self.options, |path| {
+                status.get(path).cloned().unwrap_or(FileStatus::NotMatched)
+            }))
+        } else {
+            None
+        };
+
+        Ok(ProjectOutput {
+            llm_note: self.llm_note.clone(),
+            files,
+            tree_view: tree.as_ref().map(TreeNode::render_text),
+            tree,
+            diffs,
+            warnings,
+            compaction,
+            redactions,
+        })
+    }
+
+    /// スナップショットと比べる「現在のファイル」（`snapdiff` 用）
+    ///
+    /// include・exclude・拒否リストで選んで読み込めたファイルをそのまま返す。`GIT_CHANGED`・
+    /// `DIFF_CONTENT`・サイズ上限は適用しない（それらで外れたファイルが削除されたように見えないように）。
+    pub fn selected_files(&self) -> Result<Vec<FileInfo>, String> {
+        self.check_valid()?;
+        let options = CollectOptions {
+            git_selection: GitSelection::Off,
+            diff: None,
+            limits: SizeLimits::default(),
+            metadata: MetadataFields::NONE,
+            ..self.options.clone()
+        };
+        let inc_patterns = PathMatcher::compile(self.include.iter().map(|s| s.as_str()));
+        let exc_patterns = PathMatcher::compile(self.exclude.iter().map(|s| s.as_str()));
+        let (files, _) = collect_target_files(&self.base_dir, &inc_patterns, &exc_patterns, &options)?;
+        Ok(files)
+    }
+
+    /// ツリー表示のみを生成する（ファイルは読まず、include パターンに一致するものを含めたとみなす）
+    pub fn tree_view(&self) -> String {
+        self.tree_nodes().render_text()
+    }
+
+    /// 構造化したツリーのみを生成する（`tree_view` と同じく、ファイルは読まない）
+    ///
+    /// git の変更を取得できなかった場合は、どのファイルも含めていないものとして表示する。
+    pub fn tree_nodes(&self) -> TreeNode {
+        let inc_patterns = self.include_matcher().unwrap_or_default();
+        let exc_patterns = PathMatcher::compile(self.exclude.iter().map(|s| s.as_str()));
+        build_tree(&self.base_dir, &exc_patterns, &self.options, |path| {
+            if inc_patterns.is_match(path) {
+                FileStatus::Included(None)
+            } else {
+                FileStatus::NotMatched
+            }
+        })
+    }
+
+    /// 収集対象（include に一致し、exclude・拒否リストに一致しない）ファイルの git の差分
+    ///
+    /// 作業ツリーとの差分では、`files` のうち未追跡のファイルを全行追加の差分として加える。
+    fn collect_diffs(
+        &self,
+        spec: &DiffSpec,
+        inc_patterns: &PathMatcher,
+        exc_patterns: &PathMatcher,
+        files: &[FileInfo],
+    ) -> Result<Vec<FileDiff>, String> {
+        let deny = deny_matcher(&self.options);
+        let selected = |path: &str| {
+            inc_patterns.is_match(path)
+                && !exc_patterns.is_match(path)
+                && !deny.is_match(path)
+                && !path.contains("target/backup")
+        };
+
+        let mut diffs: Vec<FileDiff> = file_diffs(&self.base_dir, spec)?
+            .into_iter()
+            .filter(|d| selected(&d.file_name))
+            .collect();
+        if spec.to.is_none() {
+            let untracked = untracked_files(&self.base_dir)?;
+            for file in files.iter().filter(|f| untracked.contains(&f.file_name)) {
+                diffs.push(added_file_diff(&file.file_name, &file.file_content));
+            }
+        }
+        // ファイルと同じ順に並べる（含まれないファイル・削除されたファイルはパス順で後ろ）
+        diffs.sort_by_cached_key(|d| {
+            let index = files.iter().position(|f| f.file_name == d.file_name).unwrap_or(usize::MAX);
+            (index, d.file_name.clone())
+        });
+        Ok(diffs)
+    }
+
+    /// 不正なパターンがあれば、まとめてエラーにする
+    fn check_valid(&self) -> Result<(), String> {
+        let errors = self.validate();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        Err(format!("不正なパターンがあります:\n{}", lines.join("\n")))
+    }
+
+    /// 収集するファイルの判定器
+    ///
+    /// git の変更から選ぶ場合は、変更されたファイルのパスだけに一致する判定器を作る
+    /// （exclude パターンと拒否リストはそのまま適用される）。
+    fn include_matcher(&self) -> Result<PathMatcher, String> {
+        if self.options.git_selection.is_off() {
+            return Ok(PathMatcher::compile(self.include.iter().map(|s| s.as_str())));
+        }
+        let changed = changed_files(&self.base_dir, &self.options.git_selection)?;
+        let patterns: Vec<String> = changed.iter().map(|p| format!("^{}$", regex::escape(p))).collect();
+        Ok(PathMatcher::compile(patterns.iter().map(|s| s.as_str())))
+    }
+}
+
+/// 秘密情報を伏せ字にする（行番号を保つので、アウトライン・圧縮より先に行う）
+fn redact_contents(files: &mut [FileInfo], diffs: &mut [FileDiff], options: &CollectOptions) -> Vec<Redaction> {
+    let redactor = Redactor::new(!options.keep_secrets, &options.redact_patterns);
+    if redactor.is_empty() {
+        return Vec::new();
+    }
+
+    let mut redactions = Vec::new();
+    for file in files.iter_mut() {
+        let found = redactor.redact(&file.file_name, &mut file.file_content);
+        if found.is_empty() {
+            continue;
+        }
+        if file.tokens.is_some() {
+            file.tokens = Some(count_tokens(&file.file_content, options.token_encoding));
+        }
+        redactions.extend(found);
+    }
+    // 差分の中の行番号は差分テキストの行
+    for diff in diffs.iter_mut() {
+        let label = format!("{} (差分)", diff.file_name);
+        redactions.extend(redactor.redact(&label, &mut diff.diff));
+    }
+    redactions
+}
+
+/// 読み込んだ内容を加工する（アウトライン → 圧縮 → 行番号）
+///
+/// アウトラインや圧縮で行を削っても、行番号は元のファイルの番号を付ける。
+/// アウトラインを作れなかったファイルは全文のまま警告に載せる。
+/// 内容を減らしたファイルがあれば、減ったトークン数を返す。
+fn transform_contents(
+    files: &mut [FileInfo],
+    options: &CollectOptions,
+    warnings: &mut Vec<FileWarning>,
+) -> Option<CompactionReport> {
+    let rules: Vec<(Regex, CompactModes)> = options
+        .compact_rules
+        .iter()
+        .filter_map(|r| Some((Regex::new(&r.pattern).ok()?, CompactModes::from_list(&r.modes).ok()?)))
+        .collect();
+    let outlines: Vec<Regex> = options.outline_patterns.iter().filter_map(|p| Regex::new(p).ok()).collect();
+    if rules.is_empty() && outlines.is_empty() && !options.line_numbers {
+        return None;
+    }
+
+    let mut report = CompactionReport::default();
+    for file in files.iter_mut() {
+        let language = language_for_path(&file.file_name);
+        let modes = rules
+            .iter()
+            .find(|(re, _)| re.is_match(&file.file_name))
+            .map(|(_, m)| *m)
+            .filter(|m| !m.is_empty());
+        let outline = if outlines.iter().any(|re| re.is_match(&file.file_name)) {
+            match outline_lines(&file.file_content, language) {
+                Ok(lines) => Some(lines),
+                Err(reason) => {
+                    warnings.push(FileWarning {
+                        file_name: file.file_name.clone(),
+                        reason,
+                    });
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let reduced = outline.is_some() || modes.is_some();
+        if !reduced && !options.line_numbers {
+            continue;
+        }
+
+        let mut lines: Vec<(usize, String)> = outline.unwrap_or_else(|| {
+            file.file_content
+                .lines()
+                .enumerate()
+                .map(|(i, l)| (i + 1, l.to_string()))
+                .collect()
+        });
+        if let Some(m) = modes {
+            // compact_lines の番号は渡したテキストでの行なので、元の行番号に引き直す
+            let text: String = lines.iter().map(|(_, l)| format!("{}\n", l)).collect();
+            lines = compact_lines(&text, language, m)
+                .into_iter()
+                .map(|(n, l)| (lines[n - 1].0, l))
+                .collect();
+        }
+
+        if reduced {
+            let reduced_text: String = lines.iter().map(|(_, l)| format!("{}\n", l)).collect();
+            report.files += 1;
+            report.tokens_before += count_tokens(&file.file_content, options.token_encoding);
+            report.tokens_after += count_tokens(&reduced_text, options.token_encoding);
+            file.file_content = reduced_text;
+        }
+        if options.line_numbers {
+            file.file_content = number_lines(lines.iter().map(|(n, l)| (*n, l.as_str())));
+        }
+        // メタデータのトークン数は出力される内容で数え直す
+        if file.tokens.is_some() {
+            file.tokens = Some(count_tokens(&file.file_content, options.token_encoding));
+        }
+    }
+
+    (report.files > 0).then_some(report)
+}
@@ -0,0 +1,545 @@
+use std::ops::Range;
+
+/// 圧縮の種類（設定ファイルの `COMPACT:<種類,...>:<正規表現>`）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompactModes {
+    /// コメントを削除（文字列リテラル内は残す）
+    pub comments: bool,
+    /// 空行と行末の空白を削除
+    pub blank: bool,
+    /// 先頭のライセンス・著作権表示のコメントを削除
+    pub license: bool,
+}
+
+impl CompactModes {
+    /// `comments,blank,license`（または `all`）を解釈する。不明な名前があればそれを返す
+    pub fn from_list(list: &str) -> Result<CompactModes, String> {
+        let mut modes = CompactModes::default();
+        for name in list.split(',').map(|n| n.trim().to_ascii_lowercase()) {
+            match name.as_str() {
+                "all" => {
+                    modes = CompactModes {
+                        comments: true,
+                        blank: true,
+                        license: true,
+                    }
+                }
+                "comments" | "comment" => modes.comments = true,
+                "blank" | "blanks" | "whitespace" => modes.blank = true,
+                "license" | "licence" => modes.license = true,
+                "" => {}
+                _ => return Err(name),
+            }
+        }
+        Ok(modes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.comments || self.blank || self.license)
+    }
+}
+
+/// 圧縮で減ったトークン数
+#[derive(Clone, Debug, Default)]
+pub struct CompactionReport {
+    /// 圧縮したファイル数
+    pub files: usize,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+}
+
+impl CompactionReport {
+    pub fn saved(&self) -> usize {
+        self.tokens_before.saturating_sub(self.tokens_after)
+    }
+
+    /// "3 ファイル: 1,200 → 800 tokens (400 削減)" のような要約
+    pub fn summary(&self) -> String {
+        format!(
+            "{} ファイル: {} → {} tokens ({} 削減)",
+            self.files,
+            self.tokens_before,
+            self.tokens_after,
+            self.saved()
+        )
+    }
+}
+
+/// 言語ごとのコメント・文字列の書き方
+struct Syntax {
+    line_comment: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// ブロックコメントが入れ子になる（Rust）
+    nested_block: bool,
+    quotes: &'static [char],
+    /// `'` を文字リテラルとしてだけ扱う（Rust のライフタイム `'a` と区別する）
+    char_literal_quote: bool,
+    /// Python の `"""` / `'''`
+    triple_quotes: bool,
+    /// Rust の `r"..."` / `r#"..."#`
+    rust_raw_strings: bool,
+    /// 行コメントの記号の直前が行頭か空白のときだけコメントとみなす（シェルの `${#var}` 等）
+    comment_needs_space: bool,
+    /// JavaScript の正規表現リテラル `/.../`
+    regex_literals: bool,
+}
+
+const RUST: Syntax = Syntax {
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_block: true,
+    quotes: &['"', '\''],
+    char_literal_quote: true,
+    triple_quotes: false,
+    rust_raw_strings: true,
+    comment_needs_space: false,
+    regex_literals: false,
+};
+
+const C_FAMILY: Syntax = Syntax {
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_block: false,
+    quotes: &['"', '\''],
+    char_literal_quote: false,
+    triple_quotes: false,
+    rust_raw_strings: false,
+    comment_needs_space: false,
+    regex_literals: false,
+};
+
+/// Go（バッククォートの文字列がある）
+const C_FAMILY_BACKTICK: Syntax = Syntax {
+    quotes: &['"', '\'', '`'],
+    ..C_FAMILY
+};
+
+/// JavaScript / TypeScript（バッククォートの文字列と正規表現リテラルがある）
+const JAVASCRIPT: Syntax = Syntax {
+    regex_literals: true,
+    ..C_FAMILY_BACKTICK
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comment: &["#"],
+    block_comment: None,
+    nested_block: false,
+    quotes: &['"', '\''],
+    char_literal_quote: false,
+    triple_quotes: true,
+    rust_raw_strings: false,
+    comment_needs_space: false,
+    regex_literals: false,
+};
+
+/// シェル・Ruby・YAML・TOML など `#` で始まるコメント
+const HASH: Syntax = Syntax {
+    line_comment: &["#"],
+    block_comment: None,
+    nested_block: false,
+    quotes: &['"', '\''],
+    char_literal_quote: false,
+    triple_quotes: false,
+    rust_raw_strings: false,
+    comment_needs_space: true,
+    regex_literals: false,
+};
+
+/// `lang::language_for_path` の言語名からコメントの書き方を決める（非対応なら None）
+fn syntax_for(language: &str) -> Option<&'static Syntax> {
+    match language {
+        "rust" => Some(&RUST),
+        "javascript" | "jsx" | "typescript" | "tsx" => Some(&JAVASCRIPT),
+        "go" => Some(&C_FAMILY_BACKTICK),
+        "c" | "cpp" | "csharp" | "java" | "kotlin" | "scala" | "swift" | "dart" | "protobuf" => {
+            Some(&C_FAMILY)
+        }
+        "python" => Some(&PYTHON),
+        "bash" | "ruby" | "perl" | "r" | "yaml" | "toml" | "makefile" | "dockerfile"
+        | "powershell" | "elixir" => Some(&HASH),
+        _ => None,
+    }
+}
+
+/// ファイル内容を圧縮し、残った行を (元の行番号, 内容) で返す
+///
+/// 行番号を元のまま保つため、行を詰めるのではなく行単位で取捨する。
+/// コメントだけの行は削除し、コード行の末尾のコメントは取り除く。
+pub fn compact_lines(text: &str, language: &str, modes: CompactModes) -> Vec<(usize, String)> {
+    let syntax = syntax_for(language);
+
+    let license = match (modes.license, syntax) {
+        (true, Some(syn)) => license_header(text, syn),
+        _ => 0..0,
+    };
+    let stripped = match (modes.comments, syntax) {
+        (true, Some(syn)) => strip_comments(text, syn),
+        _ => text.to_string(),
+    };
+
+    let original: Vec<&str> = text.lines().collect();
+    stripped
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            if license.contains(&i) {
+                return None;
+            }
+            let was_blank = original.get(i).is_none_or(|l| l.trim().is_empty());
+            if line.trim().is_empty() && (!was_blank || modes.blank) {
+                return None;
+            }
+            let line = if modes.comments || modes.blank { line.trim_end() } else { line };
+            Some((i + 1, line.to_string()))
+        })
+        .collect()
+}
+
+/// コメントを取り除く（改行は残すので行数は変わらない）
+fn strip_comments(text: &str, syn: &Syntax) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let n = chars.len();
+    let mut out = String::with_capacity(text.len());
+    let starts_with = |i: usize, pat: &str| {
+        pat.chars().enumerate().all(|(k, p)| chars.get(i + k) == Some(&p))
+    };
+
+    let mut i = 0;
+    while i < n {
+        let c = chars[i];
+        let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+
+        // Rust の生文字列 r"..." / r#"..."#
+        if syn.rust_raw_strings && c == 'r' && !prev.is_some_and(is_ident_char) {
+            let mut j = i + 1;
+            while j < n && chars[j] == '#' {
+                j += 1;
+            }
+            if j < n && chars[j] == '"' {
+                let hashes = j - i - 1;
+                let close: String = std::iter::once('"').chain(std::iter::repeat_n('#', hashes)).collect();
+                let mut k = j + 1;
+                while k < n && !starts_with(k, &close) {
+                    k += 1;
+                }
+                let end = (k + close.chars().count()).min(n);
+                out.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+        }
+
+        // Python の三重引用符
+        if syn.triple_quotes && (c == '"' || c == '\'') {
+            let triple: String = [c, c, c].iter().collect();
+            if starts_with(i, &triple) {
+                let mut k = i + 3;
+                while k < n && !starts_with(k, &triple) {
+                    k += if chars[k] == '\\' { 2 } else { 1 };
+                }
+                let end = (k + 3).min(n);
+                out.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+        }
+
+        // 文字列・文字リテラル
+        if syn.quotes.contains(&c) {
+            let is_literal = !(syn.char_literal_quote && c == '\'')
+                || chars.get(i + 1) == Some(&'\\')
+                || chars.get(i + 2) == Some(&'\'');
+            if is_literal {
+                let mut k = i + 1;
+                while k < n && chars[k] != c {
+                    // 閉じていない引用符で残り全体を文字列扱いしないよう、` 以外は行末で打ち切る
+                    if chars[k] == '\n' && c != '`' && !syn.rust_raw_strings {
+                        break;
+                    }
+                    k += if chars[k] == '\\' { 2 } else { 1 };
+                }
+                let end = (k + 1).min(n);
+                out.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+        }
+
+        // 行コメント（シバン #! は残す）
+        let line_comment = syn.line_comment.iter().any(|tok| starts_with(i, tok))
+            && (!syn.comment_needs_space || prev.is_none_or(char::is_whitespace))
+            && !(i == 0 && starts_with(i, "#!"));
+        if line_comment {
+            while i < n && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        // ブロックコメント（改行だけ残す）
+        if let Some((open, close)) = syn.block_comment {
+            if starts_with(i, open) {
+                let mut depth = 1;
+                i += open.len();
+                while i < n && depth > 0 {
+                    if syn.nested_block && starts_with(i, open) {
+                        depth += 1;
+                        i += open.len();
+                    } else if starts_with(i, close) {
+                        depth -= 1;
+                        i += close.len();
+                    } else {
+                        if chars[i] == '\n' {
+                            out.push('\n');
+                        }
+                        i += 1;
+                    }
+                }
+                continue;
+            }
+        }
+
+        // 正規表現リテラル（`/\/\//g` の中の `//` をコメントとみなさない）
+        if syn.regex_literals && c == '/' && starts_regex(&out) {
+            let mut k = i + 1;
+            let mut in_class = false;
+            while k < n && chars[k] != '\n' {
+                match chars[k] {
+                    '\\' => {
+                        k += 2;
+                        continue;
+                    }
+                    '[' => in_class = true,
+                    ']' => in_class = false,
+                    '/' if !in_class => break,
+                    _ => {}
+                }
+                k += 1;
+            }
+            // 行内で閉じていなければ割り算とみなす
+            if k < n && chars[k] == '/' {
+                out.extend(&chars[i..=k]);
+                i = k + 1;
+                continue;
+            }
+        }
+
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// 直後の `/` が正規表現リテラルになるキーワード（それ以外の語の後は割り算）
+const REGEX_KEYWORDS: &[&str] = &[
+    "return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case", "do",
+    "else", "yield", "await",
+];
+
+/// `before` の後の `/` が正規表現リテラルの始まりか
+///
+/// 直前が演算子・`(`・`,`・`=` 等やキーワードなら正規表現、値（識別子・数値・`)`・`]`・文字列）なら割り算。
+fn starts_regex(before: &str) -> bool {
+    let before = before.trim_end();
+    let Some(last) = before.chars().next_back() else {
+        return true;
+    };
+    if is_ident_char(last) || last == '$' {
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_ident_char(c) || c == '$')
+            .last()
+            .map_or(0, |(i, _)| i);
+        return REGEX_KEYWORDS.contains(&&before[start..]);
+    }
+    !matches!(last, ')' | ']' | '"' | '\'' | '`')
+}
+
+/// ライセンス表示とみなす語
+const LICENSE_KEYWORDS: &[&str] = &[
+    "license",
+    "licence",
+    "copyright",
+    "spdx-license-identifier",
+    "all rights reserved",
+    "著作権",
+];
+
+/// 先頭（シバン・空行の後）のコメントがライセンス表示なら、その行範囲（0 始まり）を返す
+fn license_header(text: &str, syn: &Syntax) -> Range<usize> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut start = 0;
+    while start < lines.len() {
+        let t = lines[start].trim();
+        if t.is_empty() || (start == 0 && t.starts_with("#!")) {
+            start += 1;
+        } else {
+            break;
+        }
+    }
+    let Some(first) = lines.get(start).map(|l| l.trim()) else {
+        return 0..0;
+    };
+
+    let mut end = start;
+    match syn.block_comment {
+        Some((open, close)) if first.starts_with(open) => {
+            while end < lines.len() {
+                let closes = if end == start {
+                    first[open.len()..].contains(close)
+                } else {
+                    lines[end].contains(close)
+                };
+                end += 1;
+                if closes {
+                    break;
+                }
+            }
+        }
+        _ => {
+            let is_line_comment =
+                |l: &str| syn.line_comment.iter().any(|tok| l.trim_start().starts_with(tok));
+            while end < lines.len() && is_line_comment(lines[end]) {
+                end += 1;
+            }
+        }
+    }
+    if end == start {
+        return 0..0;
+    }
+
+    let header = lines[start..end].join("\n").to_lowercase();
+    if LICENSE_KEYWORDS.iter().any(|k| header.contains(k)) {
+        start..end
+    } else {
+        0..0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(language: &str, text: &str) -> String {
+        strip_comments(text, syntax_for(language).unwrap())
+    }
+
+    #[test]
+    fn js_regex_literal_is_not_a_comment() {
+        assert_eq!(strip("javascript", "const re = /\\/\\//g; // c"), "const re = /\\/\\//g; ");
+        assert_eq!(strip("typescript", "if (/[/*]+/.test(s)) {} /* c */"), "if (/[/*]+/.test(s)) {} ");
+        assert_eq!(strip("javascript", "return /a\\/b/.test(s); // c"), "return /a\\/b/.test(s); ");
+        assert_eq!(strip("tsx", "f(x, /\"/); // c"), "f(x, /\"/); ");
+    }
+
+    #[test]
+    fn js_division_is_not_a_regex() {
+        assert_eq!(strip("javascript", "let a = b / c; // half"), "let a = b / c; ");
+        assert_eq!(strip("javascript", "let a = (b) / 2 / 3; // x"), "let a = (b) / 2 / 3; ");
+        assert_eq!(strip("typescript", "let a = xs[0] / 2; /* x */ let b = 1 / 2;"), "let a = xs[0] / 2;  let b = 1 / 2;");
+    }
+
+    #[test]
+    fn js_strings_and_template_literals_are_kept() {
+        assert_eq!(strip("javascript", "const u = \"http://x\"; // c"), "const u = \"http://x\"; ");
+        assert_eq!(strip("javascript", "const u = 'a /* b */'; /* c */"), "const u = 'a /* b */'; ");
+        assert_eq!(
+            strip("typescript", "const t = `a // b\n/* c */ ${x}`; // d\nnext();"),
+            "const t = `a // b\n/* c */ ${x}`; \nnext();"
+        );
+    }
+
+    #[test]
+    fn js_block_comments_do_not_nest() {
+        assert_eq!(strip("javascript", "/* a /* b */ let x = 1;"), " let x = 1;");
+        assert_eq!(strip("javascript", "a();\n/* b\nc */\nd();"), "a();\n\n\nd();");
+    }
+
+    #[test]
+    fn rust_nested_block_comments_and_literals() {
+        assert_eq!(strip("rust", "/* a /* b */ c */ let x = 1; // d"), " let x = 1; ");
+        assert_eq!(strip("rust", "let s = \"// x\"; // c"), "let s = \"// x\"; ");
+        assert_eq!(strip("rust", "let s = r#\"/* \"x\" */\"#; // c"), "let s = r#\"/* \"x\" */\"#; ");
+        assert_eq!(strip("rust", "let q = '\"'; // c"), "let q = '\"'; ");
+        // ライフタイムを文字リテラルとみなさない
+        assert_eq!(strip("rust", "fn f<'a>(x: &'a str) {} // c"), "fn f<'a>(x: &'a str) {} ");
+        // Rust には正規表現リテラルがない
+        assert_eq!(strip("rust", "let a = b / c; // d / e"), "let a = b / c; ");
+    }
+
+    #[test]
+    fn c_family_strings_and_block_comments() {
+        assert_eq!(strip("c", "char *s = \"/* not */\"; /* c */"), "char *s = \"/* not */\"; ");
+        assert_eq!(strip("java", "char q = '\\''; // c"), "char q = '\\''; ");
+        assert_eq!(strip("cpp", "/* a /* b */ int x;"), " int x;");
+    }
+
+    #[test]
+    fn go_raw_strings_are_kept() {
+        assert_eq!(strip("go", "s := `// raw /* x */` // c"), "s := `// raw /* x */` ");
+        assert_eq!(strip("go", "x := a / b // c"), "x := a / b ");
+    }
+
+    #[test]
+    fn python_strings_and_triple_quotes() {
+        assert_eq!(strip("python", "s = \"# not\"  # c"), "s = \"# not\"  ");
+        assert_eq!(strip("python", "\"\"\"# doc\n'x'\"\"\"\nx = 1  # c"), "\"\"\"# doc\n'x'\"\"\"\nx = 1  ");
+    }
+
+    #[test]
+    fn hash_comments_need_space_and_keep_shebang() {
+        assert_eq!(strip("bash", "#!/bin/sh\necho ${#arr[@]} # count"), "#!/bin/sh\necho ${#arr[@]} ");
+        assert_eq!(strip("bash", "echo '# x' \"#y\" # c"), "echo '# x' \"#y\" ");
+    }
+
+    #[test]
+    fn compact_lines_keeps_original_line_numbers() {
+        let text = "// comment only\nfn a() {} // tail\n\n/* b\n */\nfn b() {}\n";
+        let modes = CompactModes {
+            comments: true,
+            ..CompactModes::default()
+        };
+        assert_eq!(
+            compact_lines(text, "rust", modes),
+            [(2, "fn a() {}".to_string()), (3, String::new()), (6, "fn b() {}".to_string())]
+        );
+        let modes = CompactModes {
+            comments: true,
+            blank: true,
+            ..CompactModes::default()
+        };
+        assert_eq!(compact_lines(text, "rust", modes).len(), 2);
+    }
+
+    #[test]
+    fn license_header_is_removed() {
+        let text = "#!/usr/bin/env python\n# Copyright 2024 Example\n# MIT License\nimport os\n";
+        let modes = CompactModes {
+            license: true,
+            ..CompactModes::default()
+        };
+        let lines = compact_lines(text, "python", modes);
+        assert_eq!(lines, [(1, "#!/usr/bin/env python".to_string()), (4, "import os".to_string())]);
+
+        let text = "/* helper */\nint x;\n";
+        assert_eq!(compact_lines(text, "c", modes).len(), 2);
+    }
+
+    #[test]
+    fn unsupported_language_is_left_alone() {
+        let modes = CompactModes::from_list("all").unwrap();
+        let text = "# Title\n\n<!-- c -->\n";
+        assert_eq!(compact_lines(text, "markdown", modes), [(1, "# Title".to_string()), (3, "<!-- c -->".to_string())]);
+    }
+
+    #[test]
+    fn mode_list_rejects_unknown_names() {
+        assert!(!CompactModes::from_list("comments, blank").unwrap().license);
+        assert_eq!(CompactModes::from_list("comments,typo"), Err("typo".to_string()));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::models::CollectOptions;
+use crate::patterns::{PathMatcher, GLOB_PREFIX};
+
+/// include パターンに関係なく常に除外する、機密情報を含みがちなパス（gitignore 風グロブ）
+///
+/// `/` を含まないのでどの階層にも一致し、フォルダならその中身ごと除外する。
+pub const BUILTIN_DENY: &[&str] = &[
+    // バージョン管理のメタデータ
+    ".git",
+    ".hg",
+    ".svn",
+    // 環境変数・認証情報
+    ".env",
+    ".env.*",
+    ".npmrc",
+    ".pypirc",
+    ".netrc",
+    ".git-credentials",
+    ".aws",
+    ".ssh",
+    ".gnupg",
+    "*.tfstate",
+    "*.tfstate.*",
+    // 鍵・証明書ストア
+    "*.pem",
+    "*.key",
+    "*.p12",
+    "*.pfx",
+    "*.jks",
+    "*.keystore",
+    "*.kdbx",
+    "id_rsa*",
+    "id_dsa*",
+    "id_ecdsa*",
+    "id_ed25519*",
+];
+
+/// 拒否リストの判定器を作る
+///
+/// 組み込みの `BUILTIN_DENY` と設定ファイルの `DENY:` を使う。`DENY_OVERRIDE:` は
+/// `options.allow_denied` が有効（GUI で確認済み・CLI の `--allow-denied`）のときだけ打ち消しとして効く。
+pub fn deny_matcher(options: &CollectOptions) -> PathMatcher {
+    let mut patterns: Vec<String> = BUILTIN_DENY
+        .iter()
+        .map(|g| g.to_string())
+        .chain(options.deny_patterns.iter().cloned())
+        .map(|g| format!("{}{}", GLOB_PREFIX, g))
+        .collect();
+    if options.allow_denied {
+        patterns.extend(options.deny_overrides.iter().map(|g| format!("{}!{}", GLOB_PREFIX, g)));
+    }
+    PathMatcher::compile(patterns.iter().map(|s| s.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_list_denies_secrets_at_any_depth() {
+        let deny = deny_matcher(&CollectOptions::default());
+        for path in [
+            ".env",
+            "config/.env.local",
+            "certs/server.pem",
+            "deploy/tls.key",
+            "id_rsa",
+            "home/id_ed25519.pub",
+            "infra/terraform.tfstate",
+            "infra/terraform.tfstate.backup",
+        ] {
+            assert!(deny.is_match(path), "{}", path);
+        }
+        for dir in [".git", "sub/.ssh", ".aws", "vendor/lib/.svn"] {
+            assert!(deny.is_match_dir(dir), "{}", dir);
+        }
+    }
+
+    #[test]
+    fn similar_names_are_not_denied() {
+        let deny = deny_matcher(&CollectOptions::default());
+        for path in ["src/main.rs", ".envrc", "src/env.rs", "src/key.rs", "monkey", "docs/pem.md", ".gitignore", ".github/workflows/ci.yml"] {
+            assert!(!deny.is_match(path), "{}", path);
+        }
+        assert!(!deny.is_match_dir("src/keys"));
+    }
+
+    #[test]
+    fn deny_patterns_are_added() {
+        let options = CollectOptions {
+            deny_patterns: vec!["secrets/".to_string(), "*.sqlite".to_string()],
+            ..CollectOptions::default()
+        };
+        let deny = deny_matcher(&options);
+        assert!(deny.is_match_dir("secrets"));
+        assert!(deny.is_match("data/app.sqlite"));
+        assert!(deny.is_match(".env"));
+    }
+
+    #[test]
+    fn overrides_apply_only_when_allowed() {
+        let mut options = CollectOptions {
+            deny_overrides: vec![".env.example".to_string()],
+            ..CollectOptions::default()
+        };
+        assert!(deny_matcher(&options).is_match(".env.example"));
+
+        options.allow_denied = true;
+        let deny = deny_matcher(&options);
+        assert!(!deny.is_match(".env.example"));
+        assert!(deny.is_match(".env.local"));
+    }
+}
@@ -0,0 +1,52 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+/// デコード結果
+pub struct DecodedText {
+    pub text: String,
+    /// 判定（または指定）されたエンコーディング名（例: "UTF-8", "Shift_JIS"）
+    pub encoding: &'static str,
+    /// 変換できない文字があり U+FFFD に置き換えたか
+    pub had_errors: bool,
+}
+
+/// エンコーディング名（"Shift_JIS", "sjis", "EUC-JP", "UTF-16LE" など）を解釈する
+pub fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+/// BOM があればそのエンコーディングでデコードする
+///
+/// UTF-16 は NUL バイトを含むため、バイナリ判定より前に呼ぶこと。
+pub fn decode_with_bom(bytes: &[u8]) -> Option<DecodedText> {
+    let (encoding, bom_len) = Encoding::for_bom(bytes)?;
+    Some(decode_as(&bytes[bom_len..], encoding))
+}
+
+/// 指定したエンコーディングで UTF-8 文字列へ変換する（BOM は取り除く）
+pub fn decode_as(bytes: &[u8], encoding: &'static Encoding) -> DecodedText {
+    let (text, actual, had_errors) = encoding.decode(bytes);
+    DecodedText {
+        text: text.into_owned(),
+        encoding: actual.name(),
+        had_errors,
+    }
+}
+
+/// BOM のないテキストのエンコーディングを推定して UTF-8 に変換する
+///
+/// 正しい UTF-8 ならそのまま、そうでなければ内容から Shift_JIS / EUC-JP 等を推定する。
+pub fn decode_guess(bytes: &[u8]) -> DecodedText {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return DecodedText {
+            text: text.to_string(),
+            encoding: UTF_8.name(),
+            had_errors: false,
+        };
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, false);
+    decode_as(bytes, encoding)
+}
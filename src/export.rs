@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+
+use crate::chunk::{chunk_output, ChunkBudget};
+use crate::models::ProjectOutput;
+use crate::render::{OutputFormat, RenderContext};
+
+/// プロジェクトフォルダ名（未選択なら "NoProject"）
+pub fn project_name(project_dir: Option<&str>) -> String {
+    match project_dir {
+        Some(d) => {
+            // "." のような相対指定でもフォルダ名が取れるよう、必要なら絶対パスにする
+            let path = Path::new(d);
+            let name = match path.file_name() {
+                Some(n) => n.to_os_string(),
+                None => path
+                    .canonicalize()
+                    .ok()
+                    .and_then(|p| p.file_name().map(|n| n.to_os_string()))
+                    .unwrap_or_default(),
+            };
+            name.to_string_lossy().to_string()
+        }
+        None => "NoProject".to_string(),
+    }
+}
+
+/// テキスト出力用のラベル付きテキストを生成
+///
+/// 先頭に LLM補足、続いて `// Project: ..., Date: ...` のラベル、本文、終端コメントを付ける。
+pub fn labelled_text(
+    project_dir: Option<&str>,
+    llm_note: &str,
+    body: &str,
+    now: &DateTime<Local>,
+) -> String {
+    format!(
+        "// LLM補足:\n{}\n\n{}{}\n// End of chunk.\n",
+        llm_note,
+        project_label(project_dir, now),
+        body
+    )
+}
+
+/// `// Project: ..., Date: ...` のラベル行
+fn project_label(project_dir: Option<&str>, now: &DateTime<Local>) -> String {
+    let date_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    format!("// Project: {}, Date: {}\n", project_name(project_dir), date_str)
+}
+
+/// 出力ファイルのデフォルト名（フォルダ名_日時.txt）
+pub fn default_file_name(project_dir: Option<&str>, now: &DateTime<Local>) -> String {
+    let time_str = now.format("%Y%m%d_%H%M%S").to_string();
+    match project_dir {
+        Some(_) => format!("{}_{}.txt", project_name(project_dir), time_str),
+        None => format!("output_{}.txt", time_str),
+    }
+}
+
+/// チャンク分割をやり直す回数の上限
+const MAX_CHUNK_ATTEMPTS: usize = 8;
+
+/// 出力をファイル境界でチャンク分割し、それぞれラベル付きテキストにする
+///
+/// 各チャンクの先頭に「Part i of n」と含まれるファイルの一覧を付ける。
+/// LLM補足は最初のチャンクにだけ付ける。テンプレート形式ではラベルを付けずテンプレートに任せる。
+/// ラベルを付けた後の大きさが `budget.limit` に収まるよう、ラベルの分を差し引いて分割する。
+/// 分割は JSON の大きさで見積もるため、指定の形式で出力して収まらないチャンクがあれば
+/// 超えた割合だけ目標を小さくして分け直す（ツリー等だけで超える場合や、分け直しても収まらなければエラー）。
+pub fn labelled_chunks(
+    project_dir: Option<&str>,
+    llm_note: &str,
+    output: &ProjectOutput,
+    budget: &ChunkBudget,
+    format: OutputFormat,
+    ctx: &RenderContext,
+    now: &DateTime<Local>,
+) -> Result<Vec<String>, String> {
+    let limit = budget.limit;
+    let budget = if format.is_self_labelled() {
+        *budget
+    } else {
+        // 最初のチャンクに付くラベル（LLM補足を含む）が最も大きい
+        let label = budget.measure(&labelled_text(project_dir, llm_note, "", now));
+        if label >= budget.limit {
+            return Err(format!(
+                "チャンクサイズ ({} {}) がラベルの大きさ ({}) 以下です。",
+                budget.limit,
+                budget.unit.name(),
+                label
+            ));
+        }
+        ChunkBudget {
+            limit: budget.limit - label,
+            ..*budget
+        }
+    };
+    // ファイル・差分を含まない最初のチャンク（ツリー・テンプレート等）だけで超えるなら、分け直しても収まらない
+    let head = ProjectOutput {
+        files: Vec::new(),
+        diffs: Vec::new(),
+        ..output.clone()
+    };
+    let head_size = render_chunks(project_dir, llm_note, &head, &budget, format, ctx, now)?
+        .iter()
+        .map(|t| budget.measure(t))
+        .max()
+        .unwrap_or(0);
+    if head_size > limit {
+        return Err(format!(
+            "ファイルを含まない {} 形式のチャンク（ツリー・LLM補足等）だけで {} {} を超えます（{}）。CHUNK_LIMIT を大きくしてください。",
+            format.label(),
+            limit,
+            budget.unit.name(),
+            head_size
+        ));
+    }
+
+    let mut target = budget;
+    let mut largest = 0;
+    for _ in 0..MAX_CHUNK_ATTEMPTS {
+        let texts = render_chunks(project_dir, llm_note, output, &target, format, ctx, now)?;
+        largest = texts.iter().map(|t| budget.measure(t)).max().unwrap_or(0);
+        if largest <= limit {
+            return Ok(texts);
+        }
+        target.limit = (target.limit.saturating_mul(limit) / largest).min(target.limit - 1);
+        if target.limit == 0 {
+            break;
+        }
+    }
+    Err(format!(
+        "{} 形式のチャンクを {} {} 以内に分けられませんでした（最大 {}）。CHUNK_LIMIT を大きくしてください。",
+        format.label(),
+        limit,
+        budget.unit.name(),
+        largest
+    ))
+}
+
+/// `budget` でチャンク分割し、それぞれラベル付きテキストにする
+fn render_chunks(
+    project_dir: Option<&str>,
+    llm_note: &str,
+    output: &ProjectOutput,
+    budget: &ChunkBudget,
+    format: OutputFormat,
+    ctx: &RenderContext,
+    now: &DateTime<Local>,
+) -> Result<Vec<String>, String> {
+    let mut texts = Vec::new();
+    for chunk in chunk_output(output, budget)? {
+        if format.is_self_labelled() {
+            texts.push(chunk.render(format, ctx)?);
+            continue;
+        }
+        let body = format!("{}{}", chunk.label(), chunk.render(format, ctx)?);
+        let text = if chunk.header.part == 1 {
+            labelled_text(project_dir, llm_note, &body, now)
+        } else {
+            format!("{}{}\n// End of chunk.\n", project_label(project_dir, now), body)
+        };
+        texts.push(text);
+    }
+    Ok(texts)
+}
+
+/// チャンクファイルのパス（`name.txt` → `name_chunk_1.txt`）
+pub fn chunk_path(path: &Path, index: usize) -> std::path::PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{}_chunk_{}.{}", stem, index, ext.to_string_lossy()),
+        None => format!("{}_chunk_{}", stem, index),
+    };
+    path.with_file_name(file_name)
+}
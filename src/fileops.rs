@@ -1,101 +1,393 @@
 use std::fs;
 use std::path::Path;
-use walkdir::{WalkDir, DirEntry};
+use ignore::WalkBuilder;
 use regex::Regex;
 
-use crate::models::FileInfo;
+use crate::compact::CompactModes;
+use crate::deny::deny_matcher;
+use crate::export::project_name;
+use crate::encoding::{decode_as, decode_guess, decode_with_bom, encoding_for_label};
+use crate::metadata::fill_metadata;
+use crate::models::{CollectOptions, FileInfo, FileWarning, PatternError, PatternKind};
+use crate::patterns::{build_glob, PathMatcher, GLOB_PREFIX};
+use crate::tree::{FileStatus, TreeNode};
 
-/// ファイルを集める関数（include/exclude対応＋target/backup強制除外）
+/// ツール固有の無視ファイル名（.gitignore と同じ書式）
+pub const TOOL_IGNORE_FILE: &str = ".textreadignore";
+
+/// フォルダ走査用の WalkBuilder を生成する
+///
+/// `respect_ignore` が有効なら `.gitignore`・`.ignore`・`.git/info/exclude`・
+/// グローバル gitignore・`.textreadignore` に従って走査対象を絞る。
+/// 無効なら（従来の WalkDir と同じく）隠しファイルも含めて全て走査する。
+/// 実行ごと・プラットフォームごとに順序が変わらないよう、同じフォルダ内は名前順に走査する。
+fn walk_builder(base_dir: &str, options: &CollectOptions) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(base_dir);
+    builder
+        .standard_filters(false)
+        .hidden(false)
+        .sort_by_file_name(|a, b| a.cmp(b));
+    if options.respect_ignore {
+        builder
+            .git_ignore(true)
+            .git_exclude(true)
+            .git_global(true)
+            .ignore(true)
+            .parents(true)
+            .require_git(false)
+            .add_custom_ignore_filename(TOOL_IGNORE_FILE);
+    }
+    builder
+}
+
+/// バイナリ判定で先頭から調べるバイト数
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// ファイルを集める関数（include/exclude対応＋target/backup・拒否リストの強制除外）
+///
+/// 拒否リスト（`.git`・`.env`・秘密鍵等）に一致するフォルダは中に入らず、
+/// 一致するファイルは include に一致していても読み込まずに警告を残す。
+/// バイナリや読み込めないファイルは処理全体を失敗させず、警告として記録してスキップする。
+/// テキストは BOM・`ENCODING:` 指定・内容からの推定の順でエンコーディングを決め、UTF-8 に変換する。
 pub fn collect_target_files(
     base_dir: &str,
-    inc_patterns: &[Regex],
-    exc_patterns: &[Regex],
-) -> Result<Vec<FileInfo>, String> {
+    inc_patterns: &PathMatcher,
+    exc_patterns: &PathMatcher,
+    options: &CollectOptions,
+) -> Result<(Vec<FileInfo>, Vec<FileWarning>), String> {
     let mut results = Vec::new();
-    let base_path = Path::new(base_dir);
+    let mut warnings = Vec::new();
+
+    // エンコーディング指定（不正な名前・正規表現は無視）
+    let encoding_overrides: Vec<_> = options
+        .encoding_overrides
+        .iter()
+        .filter_map(|ov| Some((Regex::new(&ov.pattern).ok()?, encoding_for_label(&ov.encoding)?)))
+        .collect();
+    let deny = deny_matcher(options);
+
+    // filter_entry には 'static なクロージャが必要なので所有権付きで渡す
+    let filter_deny = deny.clone();
+    let filter_base = base_dir.to_string();
+    let walker = walk_builder(base_dir, options)
+        .filter_entry(move |e| {
+            let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+            !is_dir || !filter_deny.is_match_dir(&relative_path(e.path(), &filter_base))
+        })
+        .build();
 
-    for entry in WalkDir::new(base_path) {
-        let e = entry.map_err(|e| e.to_string())?;
-        if e.file_type().is_file() {
+    for entry in walker {
+        // 読めないフォルダ・壊れたシンボリックリンク等は警告に残して走査を続ける
+        let e = match entry {
+            Ok(e) => e,
+            Err(err) => {
+                warnings.push(FileWarning {
+                    file_name: walk_error_path(&err, base_dir),
+                    reason: format!("走査に失敗: {}", err),
+                });
+                continue;
+            }
+        };
+        if e.file_type().is_some_and(|t| t.is_file()) {
             let path = e.path();
 
-            // 相対パスを生成（Windowsの '\\' → '/' に置換）
-            let rel_path_str = match path.strip_prefix(base_path) {
-                Ok(p) => p.to_string_lossy().replace("\\", "/"),
-                Err(_) => path.to_string_lossy().replace("\\", "/"),
-            };
+            let rel_path_str = relative_path(path, base_dir);
 
             // ① target/backup を含むパスは強制除外（入れ子防止）
             if rel_path_str.contains("target/backup") {
                 continue;
             }
 
-            // ② includeパターン / excludeパターン判定
+            // ② 拒否リストに一致するファイルは include に関係なく読み込まない
+            if deny.is_match(&rel_path_str) {
+                if inc_patterns.is_match(&rel_path_str) && !exc_patterns.is_match(&rel_path_str) {
+                    warnings.push(FileWarning {
+                        file_name: rel_path_str.clone(),
+                        reason: "機密情報を含む可能性があるため除外（拒否リスト）".to_string(),
+                    });
+                }
+                continue;
+            }
+
+            // ③ includeパターン / excludeパターン判定
             //    → "src/backup.rs" のような文字列に対してマッチを行う
-            if is_in_patterns(&rel_path_str, inc_patterns) {
-                if !is_in_patterns(&rel_path_str, exc_patterns) {
-                    // ファイル読み込み
-                    let content = fs::read_to_string(path)
-                        .map_err(|err| format!("ファイル読み込みに失敗: {} ({})", err, rel_path_str))?;
-
-                    // 結果に追加
-                    results.push(FileInfo {
-                        file_url: path.to_string_lossy().to_string(),
+            if inc_patterns.is_match(&rel_path_str) && !exc_patterns.is_match(&rel_path_str) {
+                // OVERSIZE=skip なら大きすぎるファイルは読み込む前に除外する
+                let disk_len = e.metadata().ok().map(|m| m.len());
+                if let Some(len) = disk_len.filter(|&l| options.limits.skips_file_of(l)) {
+                    warnings.push(FileWarning {
                         file_name: rel_path_str.clone(),
-                        file_content: content,
+                        reason: format!(
+                            "サイズ上限を超えたため除外 ({} bytes > MAX_FILE_BYTES={})",
+                            len,
+                            options.limits.max_file_bytes.unwrap_or_default()
+                        ),
                     });
+                    continue;
                 }
+
+                // ファイル読み込み（失敗・バイナリ・非UTF-8は警告を残してスキップ）
+                let bytes = match fs::read(path) {
+                    Ok(b) => b,
+                    Err(err) => {
+                        warnings.push(FileWarning {
+                            file_name: rel_path_str.clone(),
+                            reason: format!("ファイル読み込みに失敗: {}", err),
+                        });
+                        continue;
+                    }
+                };
+                let encoding_override = encoding_overrides
+                    .iter()
+                    .find(|(re, _)| re.is_match(&rel_path_str))
+                    .map(|(_, enc)| *enc);
+
+                // BOM → 指定エンコーディング → バイナリ判定 → 推定 の順
+                let decoded = if let Some(d) = decode_with_bom(&bytes) {
+                    d
+                } else if let Some(enc) = encoding_override {
+                    decode_as(&bytes, enc)
+                } else if is_binary(&bytes) {
+                    warnings.push(FileWarning {
+                        file_name: rel_path_str.clone(),
+                        reason: "バイナリファイルのためスキップ".to_string(),
+                    });
+                    continue;
+                } else {
+                    decode_guess(&bytes)
+                };
+                if decoded.had_errors {
+                    warnings.push(FileWarning {
+                        file_name: rel_path_str.clone(),
+                        reason: format!(
+                            "{} として読み込みましたが、変換できない文字を U+FFFD に置き換えました",
+                            decoded.encoding
+                        ),
+                    });
+                }
+
+                // 結果に追加
+                let mut info = FileInfo {
+                    file_url: path.to_string_lossy().to_string(),
+                    file_name: rel_path_str.clone(),
+                    file_content: decoded.text,
+                    encoding: options.metadata.encoding.then(|| decoded.encoding.to_string()),
+                    ..FileInfo::default()
+                };
+                fill_metadata(
+                    &mut info,
+                    &bytes,
+                    e.metadata().ok().as_ref(),
+                    options.metadata,
+                    options.token_encoding,
+                );
+                results.push(info);
             }
         }
     }
-    Ok(results)
+    Ok((results, warnings))
 }
 
-/// 正規表現パターンチェック関数
-fn is_in_patterns(text: &str, patterns: &[Regex]) -> bool {
-    patterns.iter().any(|re| re.is_match(text))
+/// ベースフォルダからの相対パス（Windowsの '\\' → '/' に置換）
+fn relative_path(path: &Path, base_dir: &str) -> String {
+    match path.strip_prefix(Path::new(base_dir)) {
+        Ok(p) => p.to_string_lossy().replace("\\", "/"),
+        Err(_) => path.to_string_lossy().replace("\\", "/"),
+    }
 }
 
-/// ディレクトリツリー生成
+/// 走査エラーの対象パス（分からなければ空文字列）
+fn walk_error_path(err: &ignore::Error, base_dir: &str) -> String {
+    match err {
+        ignore::Error::WithPath { path, .. } => relative_path(path, base_dir),
+        ignore::Error::Loop { child, .. } => relative_path(child, base_dir),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            walk_error_path(err, base_dir)
+        }
+        _ => String::new(),
+    }
+}
+
+/// バイナリファイルかどうかを先頭部分の内容から判定する
+///
+/// NUL バイトを含むか、改行・タブ等以外の制御文字が 1 割を超えればバイナリとみなす。
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    if sample.is_empty() {
+        return false;
+    }
+    if sample.contains(&0) {
+        return true;
+    }
+    let control = sample
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    control * 10 > sample.len()
+}
+
+/// パターン文字列（1行1パターン）のうちコンパイルできないものを行番号付きで返す
 ///
-/// `exc_patterns` がフォルダパスにマッチした場合は、そのフォルダ以下をツリー表示に含めない。
-pub fn build_tree_view(base_dir: &str, exc_patterns: &[Regex]) -> String {
-    let mut lines = Vec::new();
-    for entry in WalkDir::new(base_dir)
+/// 行番号は空行も数えた 1 始まり（GUI の入力欄の行と一致する）。
+pub fn check_patterns<'a, I>(kind: PatternKind, patterns: I) -> Vec<PatternError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    patterns
         .into_iter()
-        .filter_entry(|e| should_show(e, exc_patterns, base_dir))
-    {
-        if let Ok(e) = entry {
-            let depth = e.depth();
-            let file_name = e.file_name().to_string_lossy().to_string();
-            if depth == 0 {
-                lines.push(file_name);
-            } else {
-                let prefix = "  ".repeat(depth);
-                lines.push(format!("{}{}", prefix, file_name));
+        .enumerate()
+        .filter_map(|(i, p)| check_pattern(kind, i + 1, p.trim()))
+        .collect()
+}
+
+/// Include/Exclude の 1 行を検査する（`GLOB:` 付きはグロブ、それ以外は正規表現）
+pub fn check_pattern(kind: PatternKind, line: usize, pattern: &str) -> Option<PatternError> {
+    match pattern.strip_prefix(GLOB_PREFIX) {
+        Some(glob) => {
+            let glob = glob.trim();
+            let body = glob.strip_prefix('!').unwrap_or(glob);
+            if body.trim().is_empty() {
+                return Some(PatternError {
+                    kind,
+                    line,
+                    pattern: pattern.to_string(),
+                    message: "グロブが空です".to_string(),
+                });
             }
+            build_glob(body).err().map(|e| PatternError {
+                kind,
+                line,
+                pattern: pattern.to_string(),
+                message: e.kind().to_string(),
+            })
         }
+        None => check_regex(kind, line, pattern),
     }
-    lines.join("\n")
 }
 
-/// ツリー表示対象にするかどうか判定するフィルタ関数
-fn should_show(entry: &DirEntry, exc_patterns: &[Regex], base_dir: &str) -> bool {
-    let base_path = Path::new(base_dir);
+/// 1 つの正規表現を検査する（空文字列は対象外）
+pub fn check_regex(kind: PatternKind, line: usize, pattern: &str) -> Option<PatternError> {
+    if pattern.is_empty() {
+        return None;
+    }
+    Regex::new(pattern).err().map(|e| PatternError {
+        kind,
+        line,
+        pattern: pattern.to_string(),
+        message: regex_error_message(&e),
+    })
+}
 
-    // ベースディレクトリからの相対パスを取得（Windowsの'\\' → '/'に置き換え）
-    let rel_path = match entry.path().strip_prefix(base_path) {
-        Ok(p) => p.to_string_lossy().replace("\\", "/"),
-        Err(_) => entry.path().display().to_string().replace("\\", "/"),
+/// `ENCODING:<名前>:<正規表現>` の名前と正規表現を検査する
+pub fn check_encoding_override(line: usize, encoding: &str, pattern: &str) -> Vec<PatternError> {
+    let mut errors = Vec::new();
+    if encoding_for_label(encoding).is_none() {
+        errors.push(PatternError {
+            kind: PatternKind::Encoding,
+            line,
+            pattern: encoding.trim().to_string(),
+            message: "不明なエンコーディング名です".to_string(),
+        });
+    }
+    errors.extend(check_regex(PatternKind::Encoding, line, pattern.trim()));
+    errors
+}
+
+/// `COMPACT:<種類>:<正規表現>` の種類名と正規表現を検査する
+pub fn check_compact_rule(line: usize, modes: &str, pattern: &str) -> Vec<PatternError> {
+    let mut errors = Vec::new();
+    if let Err(name) = CompactModes::from_list(modes) {
+        errors.push(PatternError {
+            kind: PatternKind::Compact,
+            line,
+            pattern: modes.trim().to_string(),
+            message: format!("不明な圧縮の種類です: {} (comments / blank / license / all)", name),
+        });
+    }
+    errors.extend(check_regex(PatternKind::Compact, line, pattern.trim()));
+    errors
+}
+
+/// regex のエラーメッセージ（複数行の図解付き）から最後の要約行だけを取り出す
+fn regex_error_message(err: &regex::Error) -> String {
+    let text = err.to_string();
+    let last = text.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("");
+    last.trim().trim_start_matches("error: ").to_string()
+}
+
+/// ディレクトリツリー生成
+///
+/// `exc_patterns` や拒否リストがフォルダパスにマッチした場合は、そのフォルダ以下をツリー表示に含めない。
+/// `options.respect_ignore` が有効なら ignore ファイルで無視されるものも表示しない。
+/// 各ファイルが出力に含まれたかどうかは `status`（相対パス → 状態）で決め、
+/// 最後に `options.tree_limits` の上限を適用する。
+pub fn build_tree<F>(base_dir: &str, exc_patterns: &PathMatcher, options: &CollectOptions, status: F) -> TreeNode
+where
+    F: Fn(&str) -> FileStatus,
+{
+    // filter_entry には 'static なクロージャが必要なので所有権付きで渡す
+    let filter_patterns = exc_patterns.clone();
+    let filter_deny = deny_matcher(options);
+    let filter_base = base_dir.to_string();
+    let walker = walk_builder(base_dir, options)
+        .filter_entry(move |e| {
+            let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+            should_show(e.path(), is_dir, &filter_patterns, &filter_deny, &filter_base)
+        })
+        .build();
+
+    // 走査は深さ優先なので、開いているフォルダをスタックに積み、浅い要素が来たら親へ畳む
+    let mut stack = vec![TreeNode::dir(&project_name(Some(base_dir)), "")];
+    let fold_to = |stack: &mut Vec<TreeNode>, len: usize| {
+        while stack.len() > len.max(1) {
+            if let Some(node) = stack.pop() {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node);
+                }
+            }
+        }
     };
 
+    for e in walker.flatten() {
+        let depth = e.depth();
+        if depth == 0 {
+            continue;
+        }
+        fold_to(&mut stack, depth);
+
+        let rel_path = relative_path(e.path(), base_dir);
+        let name = e.file_name().to_string_lossy().to_string();
+        if e.file_type().is_some_and(|t| t.is_dir()) {
+            stack.push(TreeNode::dir(&name, &rel_path));
+        } else if let Some(parent) = stack.last_mut() {
+            let size = e.metadata().map(|m| m.len()).unwrap_or(0);
+            parent.children.push(TreeNode::file(&name, &rel_path, size, status(&rel_path)));
+        }
+    }
+    fold_to(&mut stack, 1);
+
+    let mut root = stack.pop().unwrap_or_else(|| TreeNode::dir("", ""));
+    root.finish();
+    root.apply_limits(&options.tree_limits);
+    root
+}
+
+/// ツリー表示対象にするかどうか判定するフィルタ関数
+fn should_show(path: &Path, is_dir: bool, exc_patterns: &PathMatcher, deny: &PathMatcher, base_dir: &str) -> bool {
+    let rel_path = relative_path(path, base_dir);
+
     // target/backup を含むパスは強制除外
     if rel_path.contains("target/backup") {
         return false;
     }
 
-    // 除外パターンのいずれかにマッチしたら、このディレクトリ(またはファイル)以下は表示しない
-    if exc_patterns.iter().any(|re| re.is_match(&rel_path)) {
+    // 除外パターン・拒否リストのいずれかにマッチしたら、このディレクトリ(またはファイル)以下は表示しない
+    let excluded = if is_dir {
+        exc_patterns.is_match_dir(&rel_path) || deny.is_match_dir(&rel_path)
+    } else {
+        exc_patterns.is_match(&rel_path) || deny.is_match(&rel_path)
+    };
+    if excluded {
         return false;
     }
 
@@ -0,0 +1,451 @@
+use std::process::Command;
+
+use crate::models::FileDiff;
+
+/// include パターンの代わりに git の変更からファイルを選ぶモード（`GIT_CHANGED=`）
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum GitSelection {
+    /// 使わない（include パターンで選ぶ）
+    #[default]
+    Off,
+    /// 作業ツリーの変更（HEAD からの変更・ステージ済み・未追跡）
+    WorkingTree,
+    /// 指定した ref 以降の変更（コミット済みの分と作業ツリーの変更）
+    Since(String),
+    /// 指定した ref と HEAD の merge-base 以降の変更（このブランチで触ったファイル）
+    MergeBase(String),
+}
+
+impl GitSelection {
+    /// 設定ファイルの値（`working-tree` / `since:<ref>` / `merge-base:<ref>` / `off`）から解釈する
+    pub fn from_setting(val: &str) -> Option<GitSelection> {
+        let val = val.trim();
+        let reference = |r: &str| Some(r.trim().to_string()).filter(|r| !r.is_empty());
+        if let Some(r) = val.strip_prefix("since:") {
+            return reference(r).map(GitSelection::Since);
+        }
+        if let Some(r) = val.strip_prefix("merge-base:") {
+            return reference(r).map(GitSelection::MergeBase);
+        }
+        match val.to_ascii_lowercase().as_str() {
+            "off" | "" => Some(GitSelection::Off),
+            "working-tree" | "worktree" | "working" => Some(GitSelection::WorkingTree),
+            _ => None,
+        }
+    }
+
+    /// 設定ファイルに書く値
+    pub fn to_setting(&self) -> String {
+        match self {
+            GitSelection::Off => "off".to_string(),
+            GitSelection::WorkingTree => "working-tree".to_string(),
+            GitSelection::Since(r) => format!("since:{}", r),
+            GitSelection::MergeBase(r) => format!("merge-base:{}", r),
+        }
+    }
+
+    pub fn is_off(&self) -> bool {
+        *self == GitSelection::Off
+    }
+}
+
+/// 差分を取る範囲（`DIFF=`）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffSpec {
+    /// 比較元の ref
+    pub from: String,
+    /// 比較先の ref（None は作業ツリー）
+    pub to: Option<String>,
+    /// `from...to`：`from` と `to` の merge-base から `to` までの差分（`git diff A...B` と同じ）
+    pub merge_base: bool,
+}
+
+impl DiffSpec {
+    /// 設定ファイルの値から解釈する（`off` は使わない）
+    ///
+    /// `HEAD`・`main` は作業ツリーとの差分、`v1.0..v2.0` は 2 つの ref の間、
+    /// `main...HEAD` は merge-base からの差分（省略した側は HEAD）。
+    pub fn from_setting(val: &str) -> Option<DiffSpec> {
+        let val = val.trim();
+        if val.is_empty() || val.eq_ignore_ascii_case("off") {
+            return None;
+        }
+        let or_head = |r: &str| if r.trim().is_empty() { "HEAD".to_string() } else { r.trim().to_string() };
+        let range = |from: &str, to: &str, merge_base| DiffSpec {
+            from: or_head(from),
+            to: Some(or_head(to)),
+            merge_base,
+        };
+        Some(if let Some((from, to)) = val.split_once("...") {
+            range(from, to, true)
+        } else if let Some((from, to)) = val.split_once("..") {
+            range(from, to, false)
+        } else {
+            DiffSpec {
+                from: val.to_string(),
+                to: None,
+                merge_base: false,
+            }
+        })
+    }
+
+    /// 設定ファイルに書く値
+    pub fn to_setting(&self) -> String {
+        match &self.to {
+            Some(to) if self.merge_base => format!("{}...{}", self.from, to),
+            Some(to) => format!("{}..{}", self.from, to),
+            None => self.from.clone(),
+        }
+    }
+
+    /// 指定した ref がすべてコミットに解決できるか確かめる
+    pub fn verify(&self, base_dir: &str) -> Result<(), String> {
+        resolve_commit(base_dir, &self.from)?;
+        if let Some(to) = &self.to {
+            resolve_commit(base_dir, to)?;
+        }
+        Ok(())
+    }
+}
+
+/// 差分を出すときにファイルの内容をどこまで残すか（`DIFF_CONTENT=`）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffContent {
+    /// 選んだファイルすべての内容と差分
+    #[default]
+    All,
+    /// 差分のあるファイルだけ内容も出す
+    Changed,
+    /// 内容は出さず差分だけ
+    None,
+}
+
+impl DiffContent {
+    /// 設定ファイルの値から解釈する
+    pub fn from_name(name: &str) -> Option<DiffContent> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "all" => Some(DiffContent::All),
+            "changed" => Some(DiffContent::Changed),
+            "none" | "diff" => Some(DiffContent::None),
+            _ => None,
+        }
+    }
+
+    /// 設定ファイルに書く名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffContent::All => "all",
+            DiffContent::Changed => "changed",
+            DiffContent::None => "none",
+        }
+    }
+}
+
+/// `base_dir` 以下で変更・追加されたファイルの一覧（`base_dir` からの相対パス、`/` 区切り）
+///
+/// `git` コマンドで、基準のコミットと作業ツリー（ステージ済みを含む）の差分と、
+/// ignore されていない未追跡ファイルを集める。削除されたファイルは含めない。
+pub fn changed_files(base_dir: &str, selection: &GitSelection) -> Result<Vec<String>, String> {
+    let base = match selection {
+        GitSelection::Off => return Ok(Vec::new()),
+        GitSelection::WorkingTree => resolve_commit(base_dir, "HEAD")?,
+        GitSelection::Since(r) => resolve_commit(base_dir, r)?,
+        GitSelection::MergeBase(r) => {
+            let commit = resolve_commit(base_dir, r)?;
+            run_git(base_dir, &["merge-base", &commit, "HEAD"])?.trim().to_string()
+        }
+    };
+
+    let mut files = split_nul(&run_git(
+        base_dir,
+        &["diff", "--name-only", "-z", "--relative", "--diff-filter=d", &base, "--"],
+    )?);
+    files.extend(untracked_files(base_dir)?);
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// ignore されていない未追跡ファイルの一覧（`base_dir` からの相対パス）
+pub fn untracked_files(base_dir: &str) -> Result<Vec<String>, String> {
+    Ok(split_nul(&run_git(base_dir, &["ls-files", "--others", "--exclude-standard", "-z"])?))
+}
+
+/// `spec` の範囲の差分をファイルごとに分けて返す（パスは `base_dir` からの相対パス）
+///
+/// 作業ツリーとの差分でも未追跡ファイルは含まない（`added_file_diff` で補う）。
+pub fn file_diffs(base_dir: &str, spec: &DiffSpec) -> Result<Vec<FileDiff>, String> {
+    let mut from = resolve_commit(base_dir, &spec.from)?;
+    let to = match &spec.to {
+        Some(r) => Some(resolve_commit(base_dir, r)?),
+        None => None,
+    };
+    if spec.merge_base {
+        let to = to.as_deref().unwrap_or("HEAD");
+        from = run_git(base_dir, &["merge-base", &from, to])?.trim().to_string();
+    }
+    let mut args = vec![
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--relative",
+        "--find-renames",
+        "--src-prefix=a/",
+        "--dst-prefix=b/",
+        from.as_str(),
+    ];
+    if let Some(to) = &to {
+        args.push(to);
+    }
+    args.push("--");
+    Ok(split_patch(&run_git(base_dir, &args)?))
+}
+
+/// 未追跡ファイルを「全行追加」の差分にする
+pub fn added_file_diff(file_name: &str, content: &str) -> FileDiff {
+    let lines: Vec<&str> = content.lines().collect();
+    // 空のファイルは git と同じく開始行を 0 にする
+    let start = usize::from(!lines.is_empty());
+    let mut diff = format!("--- /dev/null\n+++ b/{}\n@@ -0,0 +{},{} @@", file_name, start, lines.len());
+    for line in lines {
+        diff.push_str("\n+");
+        diff.push_str(line);
+    }
+    FileDiff {
+        file_name: file_name.to_string(),
+        status: "added".to_string(),
+        diff,
+    }
+}
+
+/// `git diff` の出力をファイルごとに分ける
+///
+/// `diff --git` 行や `index` 行は除き、`--- a/...` 以降のハンク（バイナリ・名前の変更はその旨の行）を残す。
+fn split_patch(patch: &str) -> Vec<FileDiff> {
+    let mut diffs = Vec::new();
+    let mut current: Option<FileDiff> = None;
+    // 最初の `@@` までは状態・パスを表す行
+    let mut in_hunks = false;
+    for line in patch.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            diffs.extend(current.take());
+            let name = header.rsplit_once(" b/").map_or(header, |(_, p)| p);
+            current = Some(FileDiff {
+                file_name: name.to_string(),
+                status: "modified".to_string(),
+                diff: String::new(),
+            });
+            in_hunks = false;
+            continue;
+        }
+        let Some(d) = current.as_mut() else {
+            continue;
+        };
+        if !in_hunks {
+            in_hunks = line.starts_with("@@");
+            if line.starts_with("new file mode") {
+                d.status = "added".to_string();
+            } else if line.starts_with("deleted file mode") {
+                d.status = "deleted".to_string();
+            } else if let Some(name) = line.strip_prefix("rename to ") {
+                d.status = "renamed".to_string();
+                d.file_name = name.to_string();
+            } else if let Some(name) = line.strip_prefix("+++ b/") {
+                d.file_name = name.to_string();
+            }
+            let keep = in_hunks
+                || line.starts_with("--- ")
+                || line.starts_with("+++ ")
+                || line.starts_with("rename from ")
+                || line.starts_with("rename to ")
+                || line.starts_with("Binary files ");
+            if !keep {
+                continue;
+            }
+        }
+        if !d.diff.is_empty() {
+            d.diff.push('\n');
+        }
+        d.diff.push_str(line);
+    }
+    diffs.extend(current);
+    diffs
+}
+
+/// ref をコミットのハッシュに解決する（`-` で始まる値はオプションと紛らわしいので受け付けない）
+pub fn resolve_commit(base_dir: &str, reference: &str) -> Result<String, String> {
+    if reference.starts_with('-') {
+        return Err(format!("不正な ref です: {}", reference));
+    }
+    let spec = format!("{}^{{commit}}", reference);
+    run_git(base_dir, &["rev-parse", "--verify", "--quiet", &spec])
+        .map(|out| out.trim().to_string())
+        .map_err(|_| format!("git の ref が見つかりません: {}", reference))
+}
+
+/// `git -C base_dir <args>` を実行して標準出力を返す
+pub fn run_git(base_dir: &str, args: &[&str]) -> Result<String, String> {
+    // 日本語等のパスを \343\201... のように引用させない
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(base_dir)
+        .args(["-c", "core.quotePath=false"])
+        .args(args)
+        .output()
+        .map_err(|e| format!("git を実行できません: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} に失敗しました: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn split_nul(text: &str) -> Vec<String> {
+    text.split('\0').filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(from: &str, to: Option<&str>, merge_base: bool) -> DiffSpec {
+        DiffSpec {
+            from: from.to_string(),
+            to: to.map(str::to_string),
+            merge_base,
+        }
+    }
+
+    #[test]
+    fn diff_spec_from_setting() {
+        assert_eq!(DiffSpec::from_setting("HEAD"), Some(spec("HEAD", None, false)));
+        assert_eq!(DiffSpec::from_setting(" main "), Some(spec("main", None, false)));
+        assert_eq!(DiffSpec::from_setting("v1.0..v2.0"), Some(spec("v1.0", Some("v2.0"), false)));
+        assert_eq!(DiffSpec::from_setting("v1.0.."), Some(spec("v1.0", Some("HEAD"), false)));
+        assert_eq!(DiffSpec::from_setting("..v2.0"), Some(spec("HEAD", Some("v2.0"), false)));
+        assert_eq!(DiffSpec::from_setting("main...HEAD"), Some(spec("main", Some("HEAD"), true)));
+        assert_eq!(DiffSpec::from_setting("origin/main..."), Some(spec("origin/main", Some("HEAD"), true)));
+        assert_eq!(DiffSpec::from_setting("off"), None);
+        assert_eq!(DiffSpec::from_setting(""), None);
+    }
+
+    #[test]
+    fn diff_spec_round_trips() {
+        for val in ["HEAD", "v1.0..v2.0", "main...feature"] {
+            assert_eq!(DiffSpec::from_setting(val).unwrap().to_setting(), val);
+        }
+    }
+
+    #[test]
+    fn git_selection_from_setting() {
+        assert_eq!(GitSelection::from_setting("off"), Some(GitSelection::Off));
+        assert_eq!(GitSelection::from_setting(""), Some(GitSelection::Off));
+        assert_eq!(GitSelection::from_setting("Working-Tree"), Some(GitSelection::WorkingTree));
+        assert_eq!(GitSelection::from_setting("since: v1.0 "), Some(GitSelection::Since("v1.0".to_string())));
+        assert_eq!(
+            GitSelection::from_setting("merge-base:origin/main"),
+            Some(GitSelection::MergeBase("origin/main".to_string()))
+        );
+        assert_eq!(GitSelection::from_setting("since:"), None);
+        assert_eq!(GitSelection::from_setting("changed"), None);
+        for sel in [GitSelection::Off, GitSelection::WorkingTree, GitSelection::Since("a".to_string())] {
+            assert_eq!(GitSelection::from_setting(&sel.to_setting()), Some(sel));
+        }
+    }
+
+    #[test]
+    fn split_patch_by_file() {
+        let patch = "\
+diff --git a/src/a.rs b/src/a.rs
+index 1111111..2222222 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,2 +1,2 @@
+-old
++new
+ same
+diff --git a/new.txt b/new.txt
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index 4444444..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+";
+        let diffs = split_patch(patch);
+        let summary: Vec<(&str, &str)> = diffs.iter().map(|d| (d.file_name.as_str(), d.status.as_str())).collect();
+        assert_eq!(summary, [("src/a.rs", "modified"), ("new.txt", "added"), ("gone.txt", "deleted")]);
+        assert_eq!(diffs[0].diff, "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,2 +1,2 @@\n-old\n+new\n same");
+        // 削除されたファイルは `+++ /dev/null` なので元の名前のまま
+        assert!(diffs[2].diff.ends_with("-bye"));
+    }
+
+    #[test]
+    fn split_patch_renames_and_binaries() {
+        let patch = "\
+diff --git a/old name.rs b/new name.rs
+similarity index 90%
+rename from old name.rs
+rename to new name.rs
+index 1111111..2222222 100644
+--- a/old name.rs
++++ b/new name.rs
+@@ -1 +1 @@
+-a
++b
+diff --git a/img.png b/img.png
+index 5555555..6666666 100644
+Binary files a/img.png and b/img.png differ
+";
+        let diffs = split_patch(patch);
+        assert_eq!(diffs[0].file_name, "new name.rs");
+        assert_eq!(diffs[0].status, "renamed");
+        assert!(diffs[0].diff.starts_with("rename from old name.rs\nrename to new name.rs\n--- a/old name.rs"));
+        assert_eq!(diffs[1].file_name, "img.png");
+        assert_eq!(diffs[1].diff, "Binary files a/img.png and b/img.png differ");
+    }
+
+    #[test]
+    fn hunk_lines_that_look_like_headers_are_kept() {
+        let patch = "\
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+--- a/not-a-header
++++ b/not-a-header
+";
+        let diffs = split_patch(patch);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].file_name, "a.txt");
+        assert!(diffs[0].diff.ends_with("@@ -1,2 +1,2 @@\n--- a/not-a-header\n+++ b/not-a-header"));
+    }
+
+    #[test]
+    fn split_patch_of_nothing() {
+        assert!(split_patch("").is_empty());
+    }
+
+    #[test]
+    fn untracked_file_as_added_diff() {
+        let d = added_file_diff("new.txt", "a\nb\n");
+        assert_eq!(d.status, "added");
+        assert_eq!(d.diff, "--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+a\n+b");
+    }
+
+    #[test]
+    fn empty_untracked_file_starts_at_line_zero() {
+        let d = added_file_diff("empty.txt", "");
+        assert_eq!(d.diff, "--- /dev/null\n+++ b/empty.txt\n@@ -0,0 +0,0 @@");
+    }
+}
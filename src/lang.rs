@@ -0,0 +1,79 @@
+use std::path::Path;
+
+/// 拡張子・ファイル名から言語名（Markdown のコードブロックの言語タグ）を推定する
+///
+/// 判定できなければ空文字列を返す。
+pub fn language_for_path(path: &str) -> &'static str {
+    let p = Path::new(path);
+    let file_name = p
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    // 拡張子のないよく知られたファイル
+    match file_name.as_str() {
+        "dockerfile" => return "dockerfile",
+        "makefile" | "gnumakefile" => return "makefile",
+        "cmakelists.txt" => return "cmake",
+        "cargo.lock" => return "toml",
+        _ => {}
+    }
+
+    let ext = p
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "rs" => "rust",
+        "py" | "pyw" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" => "scala",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "swift" => "swift",
+        "rb" => "ruby",
+        "php" => "php",
+        "pl" | "pm" => "perl",
+        "lua" => "lua",
+        "r" => "r",
+        "dart" => "dart",
+        "ex" | "exs" => "elixir",
+        "erl" | "hrl" => "erlang",
+        "hs" => "haskell",
+        "ml" | "mli" => "ocaml",
+        "fs" | "fsx" => "fsharp",
+        "clj" | "cljs" => "clojure",
+        "vb" => "vbnet",
+        "sh" | "bash" | "zsh" => "bash",
+        "ps1" | "psm1" => "powershell",
+        "bat" | "cmd" => "batch",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "xml" | "xsd" | "xsl" | "svg" => "xml",
+        "css" => "css",
+        "scss" => "scss",
+        "sass" => "sass",
+        "less" => "less",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "ini" | "cfg" => "ini",
+        "md" | "markdown" => "markdown",
+        "tex" => "latex",
+        "proto" => "protobuf",
+        "graphql" | "gql" => "graphql",
+        "hbs" => "handlebars",
+        "csv" => "csv",
+        "txt" => "text",
+        _ => "",
+    }
+}
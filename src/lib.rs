@@ -0,0 +1,35 @@
+//! gpt-text-read のファイル収集・出力エンジン
+//!
+//! GUI（FLTK）や CLI から独立して使えるよう、ファイル収集・設定ファイル・
+//! スナップショット・テキスト出力の処理をライブラリとして公開する。
+
+pub mod models;
+pub mod settings;
+pub mod fileops;
+pub mod backup;
+pub mod export;
+pub mod encoding;
+pub mod patterns;
+pub mod tokens;
+pub mod chunk;
+pub mod compact;
+pub mod deny;
+pub mod git;
+pub mod lang;
+pub mod limits;
+pub mod linenum;
+pub mod metadata;
+pub mod order;
+pub mod outline;
+pub mod redact;
+pub mod render;
+pub mod snapdiff;
+pub mod tree;
+pub mod collector;
+
+pub use collector::Collector;
+pub use patterns::PathMatcher;
+pub use models::{
+    CollectOptions, CompactRule, EncodingOverride, FileInfo, FileWarning, LoadedSettings, PatternError, PatternKind,
+    ProjectOutput,
+};
@@ -0,0 +1,322 @@
+use crate::models::{FileInfo, FileWarning};
+use crate::tokens::{count_tokens, TokenEncoding};
+
+/// 1 ファイルの上限を超えたときの扱い
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OversizeAction {
+    /// 先頭と末尾を残して中間を `[truncated N lines]` に置き換える
+    #[default]
+    Truncate,
+    /// ファイルごと除外する
+    Skip,
+}
+
+impl OversizeAction {
+    /// 設定ファイルの値（`OVERSIZE=`）から解釈する
+    pub fn from_name(name: &str) -> Option<OversizeAction> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "truncate" => Some(OversizeAction::Truncate),
+            "skip" => Some(OversizeAction::Skip),
+            _ => None,
+        }
+    }
+
+    /// 設定ファイルに書く名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            OversizeAction::Truncate => "truncate",
+            OversizeAction::Skip => "skip",
+        }
+    }
+}
+
+/// 収集時のサイズ上限（None は無制限）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SizeLimits {
+    /// 1 ファイルのバイト数（`MAX_FILE_BYTES=`）
+    pub max_file_bytes: Option<usize>,
+    /// 1 ファイルの行数（`MAX_FILE_LINES=`）
+    pub max_file_lines: Option<usize>,
+    /// 全ファイルの合計バイト数（`MAX_TOTAL_BYTES=`）
+    pub max_total_bytes: Option<usize>,
+    /// 全ファイルの合計トークン数（`MAX_TOTAL_TOKENS=`）
+    pub max_total_tokens: Option<usize>,
+    /// 1 ファイルの上限を超えたときの扱い（`OVERSIZE=`）
+    pub oversize: OversizeAction,
+}
+
+impl SizeLimits {
+    /// ファイルを読む前にディスク上の大きさだけで除外できるか
+    pub fn skips_file_of(&self, len: u64) -> bool {
+        self.oversize == OversizeAction::Skip
+            && self.max_file_bytes.is_some_and(|max| len > max as u64)
+    }
+}
+
+/// 出力される内容にサイズ上限を適用する
+///
+/// 1 ファイルの上限を超えたものは切り詰めるか除外し、その後、先頭から順に合計の上限に
+/// 収まるものだけを残す（収まらないファイルは飛ばして次を試す）。
+/// 切り詰め・除外したファイルは警告に載せる。
+pub fn apply_limits(
+    files: &mut Vec<FileInfo>,
+    limits: &SizeLimits,
+    token_encoding: TokenEncoding,
+    warnings: &mut Vec<FileWarning>,
+) {
+    if limits.max_file_bytes.is_some() || limits.max_file_lines.is_some() {
+        files.retain_mut(|file| {
+            let lines = file.file_content.lines().count();
+            let bytes = file.file_content.len();
+            let over = match (limits.max_file_lines, limits.max_file_bytes) {
+                (Some(max), _) if lines > max => format!("{} 行 > MAX_FILE_LINES={}", lines, max),
+                (_, Some(max)) if bytes > max => format!("{} bytes > MAX_FILE_BYTES={}", bytes, max),
+                _ => return true,
+            };
+
+            if limits.oversize == OversizeAction::Skip {
+                warnings.push(FileWarning {
+                    file_name: file.file_name.clone(),
+                    reason: format!("サイズ上限を超えたため除外 ({})", over),
+                });
+                return false;
+            }
+            if let Some((text, marker)) =
+                truncate_middle(&file.file_content, limits.max_file_bytes, limits.max_file_lines)
+            {
+                file.file_content = text;
+                if file.tokens.is_some() {
+                    file.tokens = Some(count_tokens(&file.file_content, token_encoding));
+                }
+                warnings.push(FileWarning {
+                    file_name: file.file_name.clone(),
+                    reason: format!("サイズ上限を超えたため中間を省略 ({}): {}", over, marker),
+                });
+            }
+            true
+        });
+    }
+
+    if limits.max_total_bytes.is_none() && limits.max_total_tokens.is_none() {
+        return;
+    }
+    let mut total_bytes = 0;
+    let mut total_tokens = 0;
+    files.retain(|file| {
+        let bytes = file.file_content.len();
+        let tokens = match limits.max_total_tokens {
+            Some(_) => file.tokens.unwrap_or_else(|| count_tokens(&file.file_content, token_encoding)),
+            None => 0,
+        };
+        let over = match (limits.max_total_bytes, limits.max_total_tokens) {
+            (Some(max), _) if total_bytes + bytes > max => format!("MAX_TOTAL_BYTES={}", max),
+            (_, Some(max)) if total_tokens + tokens > max => format!("MAX_TOTAL_TOKENS={}", max),
+            _ => {
+                total_bytes += bytes;
+                total_tokens += tokens;
+                return true;
+            }
+        };
+        warnings.push(FileWarning {
+            file_name: file.file_name.clone(),
+            reason: format!("合計サイズの上限 ({}) に収まらないため除外", over),
+        });
+        false
+    });
+}
+
+/// 先頭と末尾を残し、中間を `[truncated N lines]` の 1 行に置き換える
+///
+/// 上限（バイト数・行数）の半分ずつを先頭と末尾に割り当てる。1 行が長すぎて行単位では
+/// 何も残せない場合（minify されたファイル等）は、文字境界で切って `[truncated N bytes]` にする。
+/// 上限に収まっていれば None。
+pub fn truncate_middle(text: &str, max_bytes: Option<usize>, max_lines: Option<usize>) -> Option<(String, String)> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let max_lines = max_lines.unwrap_or(usize::MAX);
+    let max_bytes = max_bytes.unwrap_or(usize::MAX);
+    if lines.len() <= max_lines && text.len() <= max_bytes {
+        return None;
+    }
+
+    let (head_lines, tail_lines) = (max_lines - max_lines / 2, max_lines / 2);
+    let (head_bytes, tail_bytes) = (max_bytes - max_bytes / 2, max_bytes / 2);
+
+    let mut head = 0;
+    let mut used = 0;
+    while head < lines.len() && head < head_lines && used + lines[head].len() <= head_bytes {
+        used += lines[head].len();
+        head += 1;
+    }
+    let mut tail = 0;
+    used = 0;
+    while head + tail < lines.len() && tail < tail_lines && used + lines[lines.len() - 1 - tail].len() <= tail_bytes {
+        used += lines[lines.len() - 1 - tail].len();
+        tail += 1;
+    }
+
+    if head == 0 && tail == 0 {
+        let head_end = floor_char_boundary(text, head_bytes);
+        let tail_start = ceil_char_boundary(text, text.len().saturating_sub(tail_bytes)).max(head_end);
+        let marker = format!("[truncated {} bytes]", tail_start - head_end);
+        let text = format!("{}\n{}\n{}", &text[..head_end], marker, &text[tail_start..]);
+        return Some((text, marker));
+    }
+
+    let marker = format!("[truncated {} lines]", lines.len() - head - tail);
+    let mut out = lines[..head].concat();
+    out.push_str(&marker);
+    out.push('\n');
+    out.push_str(&lines[lines.len() - tail..].concat());
+    Some((out, marker))
+}
+
+/// `index` 以下で最も近い文字境界
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut i = index.min(text.len());
+    while !text.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+/// `index` 以上で最も近い文字境界
+fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    let mut i = index.min(text.len());
+    while !text.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered_lines(n: usize) -> String {
+        (1..=n).map(|i| format!("l{}\n", i)).collect()
+    }
+
+    fn file(name: &str, content: &str) -> FileInfo {
+        FileInfo {
+            file_name: name.to_string(),
+            file_content: content.to_string(),
+            ..FileInfo::default()
+        }
+    }
+
+    fn names(files: &[FileInfo]) -> Vec<&str> {
+        files.iter().map(|f| f.file_name.as_str()).collect()
+    }
+
+    #[test]
+    fn text_within_limits_is_not_truncated() {
+        assert_eq!(truncate_middle(&numbered_lines(3), Some(100), Some(3)), None);
+        assert_eq!(truncate_middle("", Some(1), Some(1)), None);
+    }
+
+    #[test]
+    fn line_limit_keeps_head_and_tail() {
+        let (text, marker) = truncate_middle(&numbered_lines(10), None, Some(4)).unwrap();
+        assert_eq!(text, "l1\nl2\n[truncated 6 lines]\nl9\nl10\n");
+        assert_eq!(marker, "[truncated 6 lines]");
+
+        // 奇数なら先頭を多めに、1 行なら先頭だけ
+        let (text, _) = truncate_middle(&numbered_lines(10), None, Some(3)).unwrap();
+        assert_eq!(text, "l1\nl2\n[truncated 7 lines]\nl10\n");
+        let (text, _) = truncate_middle(&numbered_lines(10), None, Some(1)).unwrap();
+        assert_eq!(text, "l1\n[truncated 9 lines]\n");
+    }
+
+    #[test]
+    fn byte_limit_keeps_whole_lines() {
+        let (text, _) = truncate_middle(&"aa\n".repeat(10), Some(12), None).unwrap();
+        assert_eq!(text, "aa\naa\n[truncated 6 lines]\naa\naa\n");
+    }
+
+    #[test]
+    fn last_line_without_newline() {
+        let (text, _) = truncate_middle("a\nb\nc\nd", None, Some(2)).unwrap();
+        assert_eq!(text, "a\n[truncated 2 lines]\nd");
+    }
+
+    #[test]
+    fn long_single_line_is_cut_at_char_boundaries() {
+        let (text, marker) = truncate_middle(&"あ".repeat(100), Some(10), None).unwrap();
+        assert_eq!(marker, "[truncated 294 bytes]");
+        assert_eq!(text, "あ\n[truncated 294 bytes]\nあ");
+    }
+
+    #[test]
+    fn oversize_files_are_skipped_or_truncated() {
+        let limits = SizeLimits {
+            max_file_lines: Some(2),
+            oversize: OversizeAction::Skip,
+            ..SizeLimits::default()
+        };
+        let mut files = vec![file("small", "a\n"), file("big", &numbered_lines(5))];
+        let mut warnings = Vec::new();
+        apply_limits(&mut files, &limits, TokenEncoding::default(), &mut warnings);
+        assert_eq!(names(&files), ["small"]);
+        assert_eq!(warnings[0].file_name, "big");
+
+        let limits = SizeLimits {
+            oversize: OversizeAction::Truncate,
+            ..limits
+        };
+        let mut files = vec![FileInfo {
+            tokens: Some(999),
+            ..file("big", &numbered_lines(5))
+        }];
+        let mut warnings = Vec::new();
+        apply_limits(&mut files, &limits, TokenEncoding::default(), &mut warnings);
+        assert_eq!(files[0].file_content, "l1\n[truncated 3 lines]\nl5\n");
+        // 切り詰めた後のトークン数に更新する
+        assert!(files[0].tokens.is_some_and(|t| t < 999));
+        assert!(warnings[0].reason.contains("MAX_FILE_LINES=2"));
+    }
+
+    #[test]
+    fn total_limit_skips_files_that_do_not_fit_and_tries_the_next() {
+        let limits = SizeLimits {
+            max_total_bytes: Some(10),
+            ..SizeLimits::default()
+        };
+        let mut files = vec![file("a", "12345"), file("b", "12345678"), file("c", "123"), file("d", "123")];
+        let mut warnings = Vec::new();
+        apply_limits(&mut files, &limits, TokenEncoding::default(), &mut warnings);
+        assert_eq!(names(&files), ["a", "c"]);
+        assert_eq!(warnings.iter().map(|w| w.file_name.as_str()).collect::<Vec<_>>(), ["b", "d"]);
+    }
+
+    #[test]
+    fn total_token_limit_uses_file_tokens() {
+        let limits = SizeLimits {
+            max_total_tokens: Some(10),
+            ..SizeLimits::default()
+        };
+        let with_tokens = |name: &str, tokens| FileInfo {
+            tokens: Some(tokens),
+            ..file(name, "x")
+        };
+        let mut files = vec![with_tokens("a", 6), with_tokens("b", 6), with_tokens("c", 4)];
+        let mut warnings = Vec::new();
+        apply_limits(&mut files, &limits, TokenEncoding::default(), &mut warnings);
+        assert_eq!(names(&files), ["a", "c"]);
+        assert!(warnings[0].reason.contains("MAX_TOTAL_TOKENS=10"));
+    }
+
+    #[test]
+    fn skip_before_reading_only_when_skipping() {
+        let limits = SizeLimits {
+            max_file_bytes: Some(10),
+            ..SizeLimits::default()
+        };
+        assert!(!limits.skips_file_of(100));
+        let limits = SizeLimits {
+            oversize: OversizeAction::Skip,
+            ..limits
+        };
+        assert!(limits.skips_file_of(11));
+        assert!(!limits.skips_file_of(10));
+    }
+}
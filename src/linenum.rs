@@ -0,0 +1,124 @@
+use regex::Regex;
+
+/// 行番号と本文の区切り（`  12 | fn main() {`）
+pub const LINE_NUMBER_SEP: &str = " | ";
+
+/// 行番号付きのテキストにする
+///
+/// `lines` は (元の行番号, 行の内容) の組。圧縮等で行が抜けていても元の番号のまま付ける。
+/// 行番号は最大の番号の桁数に合わせて右寄せする。
+pub fn number_lines<'a, I>(lines: I) -> String
+where
+    I: IntoIterator<Item = (usize, &'a str)>,
+{
+    let lines: Vec<(usize, &str)> = lines.into_iter().collect();
+    let width = lines.iter().map(|(n, _)| *n).max().unwrap_or(0).to_string().len();
+    let mut out = String::new();
+    for (n, line) in lines {
+        if line.is_empty() {
+            // 空行の末尾に空白を残さない
+            out.push_str(&format!("{:>width$}{}\n", n, LINE_NUMBER_SEP.trim_end(), width = width));
+        } else {
+            out.push_str(&format!("{:>width$}{}{}\n", n, LINE_NUMBER_SEP, line, width = width));
+        }
+    }
+    out
+}
+
+/// `src/app.rs:42` のような参照（パスと行番号）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineReference {
+    pub path: String,
+    pub line: usize,
+}
+
+/// テキストの `pos`（バイト位置）にある `path:line` 形式の参照を探す
+///
+/// LLM の回答にある `src/app.rs:42` や `app.rs:42:5`、`src/app.rs#L42` に対応する。
+pub fn reference_at(text: &str, pos: usize) -> Option<LineReference> {
+    let re = Regex::new(r"([\w.\-/\\]*\w\.\w+)(?::|#L)(\d+)").ok()?;
+
+    // pos を含む行だけを調べる
+    let mut pos = pos.min(text.len());
+    while !text.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    let line_start = text[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = text[pos..].find('\n').map(|i| pos + i).unwrap_or(text.len());
+    let line = &text[line_start..line_end];
+    let offset = pos - line_start;
+
+    let found = re.captures_iter(line).find_map(|caps| {
+        let whole = caps.get(0)?;
+        if offset < whole.start() || offset > whole.end() {
+            return None;
+        }
+        Some(LineReference {
+            path: caps[1].replace('\\', "/"),
+            line: caps[2].parse().ok()?,
+        })
+    });
+    found
+}
+
+/// 行番号付きの行からその番号を取り出す（`  12 | ...` → 12）
+pub fn numbered_line(line: &str) -> Option<usize> {
+    let (num, _) = line.split_once(LINE_NUMBER_SEP.trim())?;
+    num.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(path: &str, line: usize) -> Option<LineReference> {
+        Some(LineReference {
+            path: path.to_string(),
+            line,
+        })
+    }
+
+    #[test]
+    fn numbers_are_right_aligned_to_the_widest() {
+        let text = number_lines([(9, "a"), (10, ""), (11, "  b")]);
+        assert_eq!(text, " 9 | a\n10 |\n11 |   b\n");
+        assert_eq!(number_lines([(1, "x")]), "1 | x\n");
+        assert_eq!(number_lines(std::iter::empty()), "");
+    }
+
+    #[test]
+    fn skipped_lines_keep_their_original_numbers() {
+        let text = number_lines([(1, "use a;"), (120, "fn b() {}")]);
+        assert_eq!(text, "  1 | use a;\n120 | fn b() {}\n");
+    }
+
+    #[test]
+    fn numbered_line_reads_the_number_back() {
+        assert_eq!(numbered_line("  12 | fn main() {"), Some(12));
+        assert_eq!(numbered_line("10 |"), Some(10));
+        assert_eq!(numbered_line("let a = b | c;"), None);
+        assert_eq!(numbered_line("no separator"), None);
+        for line in number_lines([(7, "x"), (8, "")]).lines() {
+            assert!(numbered_line(line).is_some(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn finds_references_under_the_cursor() {
+        let text = "see src/app.rs:42 and lib.rs:7:5\nthen C:\\proj\\src\\main.rs#L3 here";
+        assert_eq!(reference_at(text, 6), reference("src/app.rs", 42));
+        // 末尾（行番号の直後）も対象
+        assert_eq!(reference_at(text, 17), reference("src/app.rs", 42));
+        assert_eq!(reference_at(text, 25), reference("lib.rs", 7));
+        let pos = text.find("main.rs").unwrap();
+        assert_eq!(reference_at(text, pos), reference("/proj/src/main.rs", 3));
+    }
+
+    #[test]
+    fn no_reference_away_from_a_match() {
+        let text = "see src/app.rs:42 and 日本語のテキスト\nnext line";
+        assert_eq!(reference_at(text, 1), None);
+        assert_eq!(reference_at(text, text.find("日本語").unwrap() + 1), None);
+        assert_eq!(reference_at(text, text.len() + 10), None);
+    }
+}
@@ -1,14 +1,46 @@
 #![windows_subsystem = "windows"]
 
-mod models;
-mod settings;
-mod fileops;
-mod backup;
-
-// 新規追加モジュール
+// ファイル収集などの処理はライブラリ側 (lib.rs) にあり、ここは GUI/CLI のみ
 mod ui;
 mod app;
+mod cli;
 
 fn main() {
-    app::run_app();
+    // サブコマンド指定があればヘッドレス（CLI）モード、なければ GUI を起動
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        app::run_app();
+    } else {
+        attach_parent_console();
+        std::process::exit(cli::run_cli(&args));
+    }
 }
+
+/// CLI モードの出力を起動元のコンソールに出す
+///
+/// `windows_subsystem = "windows"` ではコンソールが割り当てられず標準出力・標準エラーが
+/// 捨てられるため、親プロセス（コマンドプロンプト等）のコンソールにつなぐ。
+/// リダイレクトされている場合は標準ハンドルが有効なので、そのまま使う。
+#[cfg(windows)]
+fn attach_parent_console() {
+    use std::ffi::c_void;
+
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+    }
+
+    // 失敗した場合（コンソールのない親から起動された等）は出力先がないまま続ける
+    unsafe {
+        if GetStdHandle(STD_OUTPUT_HANDLE).is_null() {
+            AttachConsole(ATTACH_PARENT_PROCESS);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_parent_console() {}
@@ -0,0 +1,152 @@
+use std::fs;
+
+use chrono::{DateTime, Local};
+use sha2::{Digest, Sha256};
+
+use crate::lang::language_for_path;
+use crate::models::FileInfo;
+use crate::tokens::{count_tokens, TokenEncoding};
+
+/// FileInfo に付けるメタデータの項目（設定ファイルの `METADATA=size,lines,...`）
+///
+/// 既定ではすべて有効。トークンを節約したいときは必要な項目だけを並べるか `none` を指定する。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetadataFields {
+    pub size: bool,
+    pub lines: bool,
+    pub modified: bool,
+    pub language: bool,
+    pub sha256: bool,
+    pub tokens: bool,
+    /// 読み込み時に判定したエンコーディング
+    pub encoding: bool,
+}
+
+impl Default for MetadataFields {
+    fn default() -> Self {
+        MetadataFields::ALL
+    }
+}
+
+impl MetadataFields {
+    pub const ALL: MetadataFields = MetadataFields {
+        size: true,
+        lines: true,
+        modified: true,
+        language: true,
+        sha256: true,
+        tokens: true,
+        encoding: true,
+    };
+
+    pub const NONE: MetadataFields = MetadataFields {
+        size: false,
+        lines: false,
+        modified: false,
+        language: false,
+        sha256: false,
+        tokens: false,
+        encoding: false,
+    };
+
+    /// `size,lines,modified,language,sha256,tokens,encoding`（または `all` / `none`）を解釈する
+    ///
+    /// 不明な項目名があればそれを返す。
+    pub fn from_list(list: &str) -> Result<MetadataFields, String> {
+        let mut fields = MetadataFields::NONE;
+        for name in list.split(',').map(|n| n.trim().to_ascii_lowercase()) {
+            match name.as_str() {
+                "all" => fields = MetadataFields::ALL,
+                "none" | "" => {}
+                "size" => fields.size = true,
+                "lines" => fields.lines = true,
+                "modified" | "mtime" => fields.modified = true,
+                "language" | "lang" => fields.language = true,
+                "sha256" | "hash" => fields.sha256 = true,
+                "tokens" => fields.tokens = true,
+                "encoding" => fields.encoding = true,
+                _ => return Err(name),
+            }
+        }
+        Ok(fields)
+    }
+
+    /// 設定ファイルに書く値
+    pub fn to_list(&self) -> String {
+        let names: Vec<&str> = [
+            (self.size, "size"),
+            (self.lines, "lines"),
+            (self.modified, "modified"),
+            (self.language, "language"),
+            (self.sha256, "sha256"),
+            (self.tokens, "tokens"),
+            (self.encoding, "encoding"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| *name)
+        .collect();
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(",")
+        }
+    }
+}
+
+/// 読み込んだファイルにメタデータを付ける
+///
+/// `bytes` は変換前のファイル内容（サイズとハッシュはこちらで計算する）。
+pub fn fill_metadata(
+    info: &mut FileInfo,
+    bytes: &[u8],
+    fs_meta: Option<&fs::Metadata>,
+    fields: MetadataFields,
+    token_encoding: TokenEncoding,
+) {
+    if fields.size {
+        info.size_bytes = Some(bytes.len() as u64);
+    }
+    if fields.lines {
+        info.line_count = Some(info.file_content.lines().count());
+    }
+    if fields.modified {
+        info.modified = fs_meta
+            .and_then(|m| m.modified().ok())
+            .map(|t| DateTime::<Local>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Secs, false));
+    }
+    if fields.language {
+        info.language = Some(language_for_path(&info.file_name))
+            .filter(|l| !l.is_empty())
+            .map(str::to_string);
+    }
+    if fields.sha256 {
+        info.sha256 = Some(format!("{:x}", Sha256::digest(bytes)));
+    }
+    if fields.tokens {
+        info.tokens = Some(count_tokens(&info.file_content, token_encoding));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_field_lists() {
+        assert_eq!(MetadataFields::from_list("all"), Ok(MetadataFields::ALL));
+        assert_eq!(MetadataFields::from_list("none"), Ok(MetadataFields::NONE));
+        let fields = MetadataFields::from_list("Size, hash,encoding").unwrap();
+        assert!(fields.size && fields.sha256 && fields.encoding);
+        assert!(!fields.lines && !fields.tokens);
+        assert_eq!(MetadataFields::from_list("size,sha265"), Err("sha265".to_string()));
+    }
+
+    #[test]
+    fn list_round_trips() {
+        for list in ["none", "size,lines,modified,language,sha256,tokens,encoding", "lines,tokens"] {
+            assert_eq!(MetadataFields::from_list(list).unwrap().to_list(), list);
+        }
+        assert_eq!(MetadataFields::default(), MetadataFields::ALL);
+    }
+}
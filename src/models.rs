@@ -1,11 +1,66 @@
+use std::fmt;
+
 use serde::Serialize;
 
+use crate::chunk::ChunkUnit;
+use crate::compact::CompactionReport;
+use crate::git::{DiffContent, DiffSpec, GitSelection};
+use crate::limits::SizeLimits;
+use crate::metadata::MetadataFields;
+use crate::order::FileOrder;
+use crate::redact::Redaction;
+use crate::tree::{TreeLimits, TreeNode};
+use crate::render::OutputFormat;
+use crate::tokens::TokenEncoding;
+
 /// ファイル情報
-#[derive(Clone, Serialize, Debug)]
+///
+/// メタデータ（encoding と size_bytes 以降）は `METADATA=` で指定した項目だけが付き、それ以外は None で JSON にも出力しない。
+#[derive(Clone, Serialize, Debug, Default)]
 pub struct FileInfo {
     pub file_url: String,
     pub file_name: String,
     pub file_content: String,
+    // 読み込み時に判定したエンコーディング（内容は UTF-8 に変換済み）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    // ファイルのバイト数（変換前）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_count: Option<usize>,
+    // 最終更新日時（RFC 3339, ローカル時刻）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    // 拡張子から推定した言語
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    // ファイル内容（変換前のバイト列）の SHA-256
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    // file_content のトークン数（TOKEN_ENCODING で計算）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<usize>,
+    // チャンク分割でファイルを分けたときの行範囲（1 始まり・両端含む）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_range: Option<[usize; 2]>,
+}
+
+/// 読み込めなかった（スキップした）ファイルの記録
+#[derive(Clone, Serialize, Debug)]
+pub struct FileWarning {
+    pub file_name: String,
+    pub reason: String,
+}
+
+/// git の差分（`DIFF=`）の 1 ファイル分
+#[derive(Clone, Serialize, Debug)]
+pub struct FileDiff {
+    pub file_name: String,
+    /// added / modified / deleted / renamed
+    pub status: String,
+    /// unified diff（`--- a/...` `+++ b/...` と `@@` のハンク）
+    pub diff: String,
 }
 
 /// 読み込み結果
@@ -14,15 +69,150 @@ pub struct ProjectOutput {
     // ★ llm_note を最初に書くことで、JSON でも文頭に出力される
     pub llm_note: Option<String>,
     pub files: Vec<FileInfo>,
+    /// ツリーを罫線で描いたテキスト（すべての出力形式と GUI のツリー表示に使う）
     pub tree_view: Option<String>,
+    /// 大きさ・推定トークン数・出力に含めたかどうか付きのツリー
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<TreeNode>,
+    // git の差分（DIFF= を指定したときのみ）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diffs: Vec<FileDiff>,
+    // スキップしたファイルがなければ JSON には出力しない
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<FileWarning>,
+    // 圧縮（COMPACT:）で減ったトークン数。ログ表示用で JSON には出力しない
+    #[serde(skip)]
+    pub compaction: Option<CompactionReport>,
+    // 伏せ字にした秘密情報の位置（値は含まない）。ログ表示用で JSON には出力しない
+    #[serde(skip)]
+    pub redactions: Vec<Redaction>,
+}
+
+/// パターンごとのエンコーディング指定（設定ファイルの `ENCODING:<名前>:<正規表現>`）
+#[derive(Clone, Debug)]
+pub struct EncodingOverride {
+    pub encoding: String,
+    pub pattern: String,
+}
+
+/// パターンごとの圧縮指定（設定ファイルの `COMPACT:<種類,...>:<正規表現>`）
+#[derive(Clone, Debug)]
+pub struct CompactRule {
+    /// `comments,blank,license` または `all`
+    pub modes: String,
+    pub pattern: String,
+}
+
+/// ファイル収集時のオプション（設定ファイルに保存される）
+#[derive(Clone, Debug, Default)]
+pub struct CollectOptions {
+    /// .gitignore / .git/info/exclude / グローバル gitignore / .textreadignore に従う
+    pub respect_ignore: bool,
+    /// 自動判定より優先するエンコーディング指定
+    pub encoding_overrides: Vec<EncodingOverride>,
+    /// トークン数を数えるエンコーディング
+    pub token_encoding: TokenEncoding,
+    /// トークン数の予算（超えたら警告）
+    pub token_budget: Option<usize>,
+    /// テキスト出力をチャンク分割する目安の大きさ（None なら既定値）
+    pub chunk_limit: Option<usize>,
+    /// `chunk_limit` の単位
+    pub chunk_unit: ChunkUnit,
+    /// コピー・テキスト出力の形式
+    pub output_format: OutputFormat,
+    /// FileInfo に付けるメタデータ
+    pub metadata: MetadataFields,
+    /// files の並び順
+    pub order: FileOrder,
+    /// 並び順に関係なく先頭に置くファイル（グロブ、書いた順）
+    pub pinned: Vec<String>,
+    /// ファイルごと・合計のサイズ上限
+    pub limits: SizeLimits,
+    /// ツリー表示の深さ・要素数の上限
+    pub tree_limits: TreeLimits,
+    /// include パターンの代わりに git の変更からファイルを選ぶ
+    pub git_selection: GitSelection,
+    /// 選んだファイルの git の差分を出す範囲（None は出さない）
+    pub diff: Option<DiffSpec>,
+    /// 差分を出すときにファイルの内容をどこまで残すか
+    pub diff_content: DiffContent,
+    /// file_content の各行に行番号を付ける
+    pub line_numbers: bool,
+    /// コメント・空行・ライセンス表示の削除（最初に一致したものを使う）
+    pub compact_rules: Vec<CompactRule>,
+    /// 関数本体を省いたアウトラインにするファイルのパターン（正規表現）
+    pub outline_patterns: Vec<String>,
+    /// 組み込みの検出器による秘密情報の伏せ字を行わない（`REDACT_SECRETS=false`）
+    pub keep_secrets: bool,
+    /// 追加で伏せ字にする正規表現（`REDACT:`）
+    pub redact_patterns: Vec<String>,
+    /// 組み込みの拒否リストに追加するグロブ（`DENY:`）
+    pub deny_patterns: Vec<String>,
+    /// 拒否リストから外すグロブ（`DENY_OVERRIDE:`）。`allow_denied` のときだけ有効
+    pub deny_overrides: Vec<String>,
+    /// `deny_overrides` を適用する（設定ファイルには書かず、GUI の確認・CLI の `--allow-denied` で決める）
+    pub allow_denied: bool,
+}
+
+/// パターンの種類（エラー表示用）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternKind {
+    Include,
+    Exclude,
+    Encoding,
+    Compact,
+    Outline,
+    Redact,
+    Deny,
+    Pin,
+    /// `KEY=値` 形式の設定の値
+    Setting,
+}
+
+impl PatternKind {
+    /// エラー表示用の名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            PatternKind::Include => "includeパターン",
+            PatternKind::Exclude => "excludeパターン",
+            PatternKind::Encoding => "エンコーディング指定",
+            PatternKind::Compact => "COMPACT指定",
+            PatternKind::Outline => "OUTLINE指定",
+            PatternKind::Redact => "伏せ字パターン",
+            PatternKind::Deny => "拒否リスト",
+            PatternKind::Pin => "PIN指定",
+            PatternKind::Setting => "設定",
+        }
+    }
+}
+
+/// コンパイルできなかったパターン
+///
+/// `line` は 1 始まりで、設定ファイルから読んだ場合はファイルの行番号、
+/// GUI の入力欄や `Collector` に渡したリストの場合はその中での行番号。
+#[derive(Clone, Debug)]
+pub struct PatternError {
+    pub kind: PatternKind,
+    pub line: usize,
+    pub pattern: String,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}行目: {} ({})", self.kind.label(), self.line, self.pattern, self.message)
+    }
 }
 
 /// 設定ファイルから読み込む内容
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct LoadedSettings {
     pub patterns_include: Vec<String>,
     pub patterns_exclude: Vec<String>,
     pub output_path: Option<String>,
     pub dev_memo: Vec<String>,
     pub llm_note: Vec<String>,
+    pub options: CollectOptions,
+    // 不正な正規表現・エンコーディング名・設定値（行番号は設定ファイルのもの）
+    pub pattern_errors: Vec<PatternError>,
 }
@@ -0,0 +1,190 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::time::SystemTime;
+
+use crate::models::FileInfo;
+use crate::patterns::{PathMatcher, GLOB_PREFIX};
+
+/// `ProjectOutput.files` の並び順（`ORDER=`）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileOrder {
+    /// パスの階層ごとに名前順（ツリー表示と同じ順）
+    #[default]
+    Path,
+    /// 先に書いた include パターンに一致するファイルから
+    Pattern,
+    /// 小さいファイルから
+    Size,
+    /// 最近更新したファイルから
+    Modified,
+}
+
+impl FileOrder {
+    /// 設定ファイルの値から解釈する
+    pub fn from_name(name: &str) -> Option<FileOrder> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "path" => Some(FileOrder::Path),
+            "pattern" | "include" => Some(FileOrder::Pattern),
+            "size" => Some(FileOrder::Size),
+            "modified" | "mtime" | "recent" => Some(FileOrder::Modified),
+            _ => None,
+        }
+    }
+
+    /// 設定ファイルに書く名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileOrder::Path => "path",
+            FileOrder::Pattern => "pattern",
+            FileOrder::Size => "size",
+            FileOrder::Modified => "modified",
+        }
+    }
+}
+
+/// ファイルを並べ替える
+///
+/// `pinned`（`PIN:` のグロブ）に一致するファイルを書いた順に先頭へ置き、残りを `order` で並べる。
+/// 同順位はパス順にするので、走査の順序やプラットフォームによらず結果は同じになる。
+/// `include` は `FileOrder::Pattern` のときに使う include パターン（1 行 1 パターン）。
+pub fn sort_files(files: &mut [FileInfo], order: FileOrder, include: &[String], pinned: &[String]) {
+    let pins: Vec<PathMatcher> = pinned
+        .iter()
+        .map(|p| PathMatcher::compile([format!("{}{}", GLOB_PREFIX, p).as_str()]))
+        .collect();
+    let patterns: Vec<PathMatcher> = match order {
+        FileOrder::Pattern => include
+            .iter()
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| PathMatcher::compile([p.as_str()]))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let first_match = |matchers: &[PathMatcher], path: &str| {
+        matchers.iter().position(|m| m.is_match(path)).unwrap_or(usize::MAX)
+    };
+    let modified = |file: &FileInfo| -> Option<SystemTime> {
+        fs::metadata(&file.file_url).ok()?.modified().ok()
+    };
+
+    // (PIN の順, 並び順の値, 更新日時の新しい順, パス)。日時が取れないものは `Reverse(None)` で最後になる
+    files.sort_by_cached_key(|f| {
+        let (rank, newest) = match order {
+            FileOrder::Path => (0, None),
+            FileOrder::Pattern => (first_match(&patterns, &f.file_name), None),
+            FileOrder::Size => (f.file_content.len(), None),
+            FileOrder::Modified => (0, modified(f)),
+        };
+        (first_match(&pins, &f.file_name), rank, Reverse(newest), path_key(&f.file_name))
+    });
+}
+
+/// パスを `/` 区切りの階層ごとに比べるためのキー（`a/b.rs` は `a.rs` より前。ツリー表示と同じ順）
+fn path_key(path: &str) -> Vec<String> {
+    path.split('/').map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn file(name: &str, content: &str) -> FileInfo {
+        FileInfo {
+            file_url: format!("/proj/{}", name),
+            file_name: name.to_string(),
+            file_content: content.to_string(),
+            ..FileInfo::default()
+        }
+    }
+
+    fn names(files: &[FileInfo]) -> Vec<&str> {
+        files.iter().map(|f| f.file_name.as_str()).collect()
+    }
+
+    fn sorted(mut files: Vec<FileInfo>, order: FileOrder, include: &[&str], pinned: &[&str]) -> Vec<FileInfo> {
+        let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+        let pinned: Vec<String> = pinned.iter().map(|s| s.to_string()).collect();
+        sort_files(&mut files, order, &include, &pinned);
+        files
+    }
+
+    fn sample() -> Vec<FileInfo> {
+        vec![
+            file("src/main.rs", "fn main() {}"),
+            file("README.md", "# readme, a bit longer"),
+            file("src.rs", "x"),
+            file("src/lib/mod.rs", "pub mod a;"),
+            file("Cargo.toml", "[package]"),
+        ]
+    }
+
+    #[test]
+    fn path_order_follows_the_tree() {
+        let files = sorted(sample(), FileOrder::Path, &[], &[]);
+        assert_eq!(names(&files), ["Cargo.toml", "README.md", "src/lib/mod.rs", "src/main.rs", "src.rs"]);
+    }
+
+    #[test]
+    fn pattern_order_follows_include_lines() {
+        let include = [r"\.toml$", "", r"^src/", r"\.md$"];
+        let files = sorted(sample(), FileOrder::Pattern, &include, &[]);
+        // 一致しないもの（src.rs）は最後、同じパターンの中はパス順
+        assert_eq!(names(&files), ["Cargo.toml", "src/lib/mod.rs", "src/main.rs", "README.md", "src.rs"]);
+    }
+
+    #[test]
+    fn size_order_is_smallest_first_then_path() {
+        let files = sorted(
+            vec![file("b.rs", "12345"), file("c.rs", "1"), file("a.rs", "12345")],
+            FileOrder::Size,
+            &[],
+            &[],
+        );
+        assert_eq!(names(&files), ["c.rs", "a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn modified_order_is_newest_first_and_unknown_last() {
+        let dir = std::env::temp_dir().join(format!("text-read-order-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        let mut files = Vec::new();
+        for (name, age) in [("old.rs", 300), ("new.rs", 10), ("mid.rs", 100)] {
+            let path = dir.join(name);
+            fs::write(&path, name).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+            files.push(FileInfo {
+                file_url: path.to_string_lossy().to_string(),
+                ..file(name, "")
+            });
+        }
+        files.push(file("missing.rs", ""));
+        let files = sorted(files, FileOrder::Modified, &[], &[]);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(names(&files), ["new.rs", "mid.rs", "old.rs", "missing.rs"]);
+    }
+
+    #[test]
+    fn pinned_files_come_first_in_pin_order() {
+        let files = sorted(sample(), FileOrder::Size, &[], &["README.md", "src/**"]);
+        assert_eq!(names(&files), ["README.md", "src/lib/mod.rs", "src/main.rs", "src.rs", "Cargo.toml"]);
+    }
+
+    #[test]
+    fn parses_order_names() {
+        assert_eq!(FileOrder::from_name(" Recent "), Some(FileOrder::Modified));
+        assert_eq!(FileOrder::from_name("include"), Some(FileOrder::Pattern));
+        assert_eq!(FileOrder::from_name("random"), None);
+        for order in [FileOrder::Path, FileOrder::Pattern, FileOrder::Size, FileOrder::Modified] {
+            assert_eq!(FileOrder::from_name(order.name()), Some(order));
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use proc_macro2::LineColumn;
+use syn::visit::{self, Visit};
+
+/// 省略した関数本体の代わりに出力する文字列
+pub const ELIDED_BODY: &str = "{ ... }";
+
+/// ファイル内容からアウトライン（関数本体を省いたもの）を作り、残った行を (元の行番号, 内容) で返す
+///
+/// 構造体・列挙型・トレイト・impl・use・定数などはそのまま残し、関数とメソッドの本体だけを
+/// `{ ... }` に置き換える。ドキュメントコメントや属性も残るので、API の形がそのまま読める。
+/// 非対応の言語や構文エラーの場合は理由を返す（呼び出し側で全文を出力する）。
+pub fn outline_lines(text: &str, language: &str) -> Result<Vec<(usize, String)>, String> {
+    match language {
+        "rust" => rust_outline(text),
+        "" => Err("アウトライン非対応のファイルのため全文を出力しました".to_string()),
+        other => Err(format!("アウトライン非対応の言語 ({}) のため全文を出力しました", other)),
+    }
+}
+
+/// Rust のソースを syn で解析して関数本体の位置を集める
+fn rust_outline(text: &str) -> Result<Vec<(usize, String)>, String> {
+    let parsed = syn::parse_file(text);
+    let result = match parsed {
+        Ok(file) => {
+            let mut finder = BodyFinder::default();
+            finder.visit_file(&file);
+            Ok(elide_bodies(text, &finder.bodies))
+        }
+        Err(e) => Err(format!(
+            "構文エラーのためアウトラインを作れず全文を出力しました ({}行目: {})",
+            e.span().start().line,
+            e
+        )),
+    };
+    // 解析のたびにスレッドごとのソース位置表が増え続けないよう解放する
+    proc_macro2::extra::invalidate_current_thread_spans();
+    result
+}
+
+/// 関数本体の `{` の位置と `}` の直後の位置
+#[derive(Default)]
+struct BodyFinder {
+    bodies: Vec<(LineColumn, LineColumn)>,
+}
+
+impl BodyFinder {
+    fn push(&mut self, block: &syn::Block) {
+        let span = block.brace_token.span;
+        self.bodies.push((span.open().start(), span.close().end()));
+    }
+}
+
+impl<'ast> Visit<'ast> for BodyFinder {
+    // 本体の中（ローカル関数など）へは降りない
+    fn visit_item_fn(&mut self, f: &'ast syn::ItemFn) {
+        self.push(&f.block);
+    }
+
+    fn visit_impl_item_fn(&mut self, f: &'ast syn::ImplItemFn) {
+        self.push(&f.block);
+    }
+
+    fn visit_trait_item_fn(&mut self, f: &'ast syn::TraitItemFn) {
+        match &f.default {
+            Some(block) => self.push(block),
+            None => visit::visit_trait_item_fn(self, f),
+        }
+    }
+}
+
+/// `bodies` の範囲を `{ ... }` に置き換える
+///
+/// 本体の `}` の後に同じ行の続き（行末のコメント等）があれば、`{ ... }` に続けて出す。
+fn elide_bodies(text: &str, bodies: &[(LineColumn, LineColumn)]) -> Vec<(usize, String)> {
+    let lines: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+    let mut bodies = bodies.to_vec();
+    bodies.sort_by_key(|(open, _)| (open.line, open.column));
+
+    let mut out = Vec::new();
+    // 組み立て中の行（元の行番号, 内容）と、まだ出力していない位置
+    let mut current = (1, String::new());
+    let mut pos = LineColumn { line: 1, column: 0 };
+
+    // pos から target までの元のテキストを current に足す（行をまたいだら確定させる）
+    let copy_until = |pos: LineColumn, target: LineColumn, current: &mut (usize, String), out: &mut Vec<(usize, String)>| {
+        for line in pos.line..=target.line {
+            let chars = lines.get(line - 1).map(|l| l.as_slice()).unwrap_or(&[]);
+            let start = if line == pos.line { pos.column } else { 0 };
+            let end = if line == target.line { target.column } else { chars.len() };
+            current.1.extend(&chars[start.min(chars.len())..end.min(chars.len())]);
+            if line < target.line {
+                out.push(std::mem::replace(current, (line + 1, String::new())));
+            }
+        }
+    };
+
+    for (open, close) in bodies {
+        if (open.line, open.column) < (pos.line, pos.column) {
+            continue;
+        }
+        copy_until(pos, open, &mut current, &mut out);
+        current.1.push_str(ELIDED_BODY);
+        pos = close;
+    }
+    let end = LineColumn {
+        line: lines.len().max(1),
+        column: lines.last().map(|l| l.len()).unwrap_or(0),
+    };
+    if (pos.line, pos.column) <= (end.line, end.column) {
+        copy_until(pos, end, &mut current, &mut out);
+    }
+    if !lines.is_empty() {
+        out.push(current);
+    }
+
+    out.into_iter()
+        .map(|(n, l)| (n, l.trim_end().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(text: &str) -> Vec<(usize, String)> {
+        outline_lines(text, "rust").unwrap()
+    }
+
+    fn line(n: usize, text: &str) -> (usize, String) {
+        (n, text.to_string())
+    }
+
+    #[test]
+    fn function_bodies_are_elided_with_original_line_numbers() {
+        let text = "/// 足す\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\nfn main() {\n    add(1, 2);\n}\n";
+        assert_eq!(
+            outline(text),
+            [line(1, "/// 足す"), line(2, "pub fn add(a: i32, b: i32) -> i32 { ... }"), line(5, ""), line(6, "fn main() { ... }")]
+        );
+    }
+
+    #[test]
+    fn types_and_impls_are_kept() {
+        let text = "#[derive(Debug)]\nstruct S {\n    x: u8,\n}\n\nimpl S {\n    const N: u8 = 1;\n    fn get(&self) -> u8 {\n        self.x\n    }\n}\n";
+        assert_eq!(
+            outline(text),
+            [
+                line(1, "#[derive(Debug)]"),
+                line(2, "struct S {"),
+                line(3, "    x: u8,"),
+                line(4, "}"),
+                line(5, ""),
+                line(6, "impl S {"),
+                line(7, "    const N: u8 = 1;"),
+                line(8, "    fn get(&self) -> u8 { ... }"),
+                line(11, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn trait_default_methods_are_elided_and_declarations_kept() {
+        let text = "trait T {\n    fn required(&self);\n    fn provided(&self) {\n        self.required();\n    }\n}\n";
+        assert_eq!(
+            outline(text),
+            [line(1, "trait T {"), line(2, "    fn required(&self);"), line(3, "    fn provided(&self) { ... }"), line(6, "}")]
+        );
+    }
+
+    #[test]
+    fn nested_functions_and_modules() {
+        let text = "mod m {\n    fn outer() {\n        fn inner() {}\n        inner();\n    }\n}\n";
+        assert_eq!(outline(text), [line(1, "mod m {"), line(2, "    fn outer() { ... }"), line(6, "}")]);
+    }
+
+    #[test]
+    fn rest_of_the_closing_line_is_kept() {
+        let text = "fn a() {\n    1;\n} // end\nfn b() {} fn c() { 2; }\n";
+        assert_eq!(outline(text), [line(1, "fn a() { ... } // end"), line(4, "fn b() { ... } fn c() { ... }")]);
+    }
+
+    #[test]
+    fn multibyte_text_before_the_body() {
+        let text = "fn あ(s: &str) -> &str { \"い\" } // う\n";
+        assert_eq!(outline(text), [line(1, "fn あ(s: &str) -> &str { ... } // う")]);
+    }
+
+    #[test]
+    fn empty_file_has_no_lines() {
+        assert!(outline("").is_empty());
+    }
+
+    #[test]
+    fn syntax_errors_and_other_languages_are_reported() {
+        let err = outline_lines("fn a( {\n", "rust").unwrap_err();
+        assert!(err.contains("構文エラー"), "{}", err);
+        assert!(outline_lines("def a(): pass\n", "python").unwrap_err().contains("python"));
+        assert!(outline_lines("text", "").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+
+/// 設定ファイル・入力欄でグロブパターンを表す接頭辞
+///
+/// Include は `GLOB:src/**/*.rs`、Exclude は設定ファイル上 `EXCLUDE_GLOB:target/**`
+/// （入力欄では Exclude 欄に `GLOB:target/**`）と書く。先頭に `!` を付けると打ち消し。
+pub const GLOB_PREFIX: &str = "GLOB:";
+
+/// 正規表現とグロブ（gitignore 風）を併用したパスの判定器
+///
+/// 正規表現・グロブのどれかに一致し、かつ `!` 付きグロブに一致しなければ「一致」とする。
+/// パスはベースフォルダからの相対パス（区切りは `/`）。
+#[derive(Clone, Debug)]
+pub struct PathMatcher {
+    regexes: Vec<Regex>,
+    globs: GlobSet,
+    negated: GlobSet,
+}
+
+impl Default for PathMatcher {
+    fn default() -> Self {
+        PathMatcher {
+            regexes: Vec::new(),
+            globs: GlobSet::empty(),
+            negated: GlobSet::empty(),
+        }
+    }
+}
+
+impl PathMatcher {
+    /// パターン文字列（1行1パターン）から判定器を作る
+    ///
+    /// `GLOB:` で始まる行はグロブ、それ以外は正規表現として扱う。
+    /// 空行とコンパイルできないパターンは読み飛ばす（事前に `check_patterns` で検査すること）。
+    pub fn compile<'a, I>(patterns: I) -> PathMatcher
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut regexes = Vec::new();
+        let mut globs = GlobSetBuilder::new();
+        let mut negated = GlobSetBuilder::new();
+
+        for p in patterns.into_iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            match p.strip_prefix(GLOB_PREFIX) {
+                Some(glob) => {
+                    let glob = glob.trim();
+                    let (target, body) = match glob.strip_prefix('!') {
+                        Some(rest) => (&mut negated, rest),
+                        None => (&mut globs, glob),
+                    };
+                    if let Ok(g) = build_glob(body) {
+                        target.add(g);
+                    }
+                }
+                None => {
+                    if let Ok(re) = Regex::new(p) {
+                        regexes.push(re);
+                    }
+                }
+            }
+        }
+
+        PathMatcher {
+            regexes,
+            globs: globs.build().unwrap_or_else(|_| GlobSet::empty()),
+            negated: negated.build().unwrap_or_else(|_| GlobSet::empty()),
+        }
+    }
+
+    /// ファイルの相対パスが一致するか
+    pub fn is_match(&self, rel_path: &str) -> bool {
+        let hit = self.regexes.iter().any(|re| re.is_match(rel_path)) || self.globs.is_match(rel_path);
+        hit && !self.negated.is_match(rel_path)
+    }
+
+    /// フォルダの相対パスが一致するか
+    ///
+    /// グロブは `dir/**` のような書き方でもフォルダ自体に一致するよう、末尾に `/` を付けても判定する。
+    pub fn is_match_dir(&self, rel_path: &str) -> bool {
+        if self.is_match(rel_path) {
+            return true;
+        }
+        let with_slash = format!("{}/", rel_path);
+        self.globs.is_match(&with_slash) && !self.negated.is_match(&with_slash)
+    }
+
+    /// パターンが 1 つもないか
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty() && self.globs.is_empty() && self.negated.is_empty()
+    }
+}
+
+/// gitignore 風のグロブを組み立てる
+///
+/// - `/` を含まないパターン（`*.rs`）はどの階層のファイル名にも一致
+/// - `/` を含むパターン（`src/**/*.rs`）はベースフォルダからの相対パスに一致
+/// - 先頭の `/` は相対パスの先頭に固定する意味で取り除き、末尾の `/` はフォルダ以下全体を表す
+/// - `*` は `/` をまたがない（またぐには `**`）
+pub fn build_glob(pattern: &str) -> Result<Glob, globset::Error> {
+    let mut pat = pattern.trim().to_string();
+    if pat.ends_with('/') {
+        pat.push_str("**");
+    }
+    let pat = match pat.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if !pat.contains('/') => format!("**/{}", pat),
+        None => pat,
+    };
+    GlobBuilder::new(&pat).literal_separator(true).build()
+}
+
+/// パス文字列をグロブのメタ文字が効かないようにエスケープする
+pub fn escape_glob(path: &str) -> String {
+    globset::escape(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(patterns: &[&str]) -> PathMatcher {
+        PathMatcher::compile(patterns.iter().copied())
+    }
+
+    #[test]
+    fn glob_without_slash_matches_any_level() {
+        let m = matcher(&["GLOB:*.rs"]);
+        assert!(m.is_match("main.rs"));
+        assert!(m.is_match("src/engine/mod.rs"));
+        assert!(!m.is_match("src/main.rs.bak"));
+    }
+
+    #[test]
+    fn glob_with_slash_is_relative_to_the_base() {
+        let m = matcher(&["GLOB:src/**/*.rs", "GLOB:/build.rs"]);
+        assert!(m.is_match("src/main.rs"));
+        assert!(m.is_match("src/a/b/c.rs"));
+        assert!(!m.is_match("tests/src/main.rs"));
+        assert!(m.is_match("build.rs"));
+        assert!(!m.is_match("sub/build.rs"));
+        // `*` は `/` をまたがない
+        assert!(!matcher(&["GLOB:src/*.rs"]).is_match("src/a/b.rs"));
+    }
+
+    #[test]
+    fn trailing_slash_means_the_whole_folder() {
+        let m = matcher(&["GLOB:target/"]);
+        assert!(m.is_match("target/debug/app"));
+        assert!(!m.is_match("src/target.rs"));
+        assert!(m.is_match_dir("target"));
+        assert!(matcher(&["GLOB:docs/**"]).is_match_dir("docs"));
+    }
+
+    #[test]
+    fn negated_glob_wins_over_regex_and_glob() {
+        let m = matcher(&[r"\.rs$", "GLOB:*.toml", "GLOB:!src/generated/**", "GLOB:!Cargo.toml"]);
+        assert!(m.is_match("src/main.rs"));
+        assert!(!m.is_match("src/generated/api.rs"));
+        assert!(m.is_match("crates/a/config.toml"));
+        assert!(!m.is_match("Cargo.toml"));
+    }
+
+    #[test]
+    fn blank_and_invalid_patterns_are_skipped() {
+        let m = matcher(&["", "  ", "GLOB:[", "("]);
+        assert!(m.is_empty());
+        assert!(!m.is_match("a.rs"));
+        assert!(!PathMatcher::default().is_match("a.rs"));
+    }
+
+    #[test]
+    fn escaped_paths_match_only_themselves() {
+        let path = "docs/[draft] *notes?.md";
+        let m = matcher(&[format!("{}/{}", GLOB_PREFIX, escape_glob(path)).as_str()]);
+        assert!(m.is_match(path));
+        assert!(!m.is_match("docs/d anything notes!.md"));
+        assert_eq!(escape_glob("a/b.rs"), "a/b.rs");
+    }
+}
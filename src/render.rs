@@ -0,0 +1,579 @@
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use handlebars::{no_escape, Handlebars};
+use serde_json::{json, Value};
+
+use crate::chunk::ChunkHeader;
+use crate::export::project_name;
+use crate::lang::language_for_path;
+use crate::models::{FileInfo, ProjectOutput};
+
+/// ユーザー定義テンプレートのファイル名（`text-read-settings.txt` と同じフォルダに置く）
+pub const TEMPLATE_FILE: &str = "text-read-template.hbs";
+
+/// テンプレートファイルがないときに使うテンプレート（従来のテキスト出力と同じ体裁）
+pub const DEFAULT_TEMPLATE: &str = "\
+{{#if llm_note}}// LLM補足:
+{{llm_note}}
+
+{{/if}}
+// Project: {{project}}, Date: {{date}}
+{{#if chunk}}
+// Part {{chunk.part}} of {{chunk.total_parts}}
+{{/if}}
+{{#if tree}}
+{{tree}}
+
+{{/if}}
+{{#each files}}
+File: {{file_name}}
+{{file_content}}
+--------------------------------
+{{/each}}
+{{#each diffs}}
+Diff: {{file_name}} ({{status}})
+{{diff}}
+--------------------------------
+{{/each}}
+// End of chunk.
+";
+
+/// コピー・テキスト出力・CLI で使う出力形式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// ProjectOutput をそのまま JSON にしたもの
+    #[default]
+    Json,
+    /// ファイルごとに見出しとコードブロックを付けた Markdown
+    Markdown,
+    /// ファイルごとに `<document>` タグで囲んだ XML（長いコンテキスト向け）
+    Xml,
+    /// `text-read-template.hbs`（Handlebars）によるユーザー定義の形式
+    Template,
+}
+
+impl OutputFormat {
+    /// GUI の選択肢・CLI の `--format` に並べる順
+    pub const ALL: [OutputFormat; 4] = [
+        OutputFormat::Json,
+        OutputFormat::Markdown,
+        OutputFormat::Xml,
+        OutputFormat::Template,
+    ];
+
+    /// 設定ファイルの値（`OUTPUT_FORMAT=`）から解釈する
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(OutputFormat::Json),
+            "markdown" | "md" => Some(OutputFormat::Markdown),
+            "xml" => Some(OutputFormat::Xml),
+            "template" => Some(OutputFormat::Template),
+            _ => None,
+        }
+    }
+
+    /// 設定ファイルに書く名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Template => "template",
+        }
+    }
+
+    /// GUI に表示する名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "JSON",
+            OutputFormat::Markdown => "Markdown",
+            OutputFormat::Xml => "XML",
+            OutputFormat::Template => "テンプレート",
+        }
+    }
+
+    /// 出力テキストそのものに見出し（Project・Date 等）を含む形式か
+    ///
+    /// テンプレートは体裁をすべて自分で決めるため、テキスト出力でラベルを付け足さない。
+    pub fn is_self_labelled(&self) -> bool {
+        matches!(self, OutputFormat::Template)
+    }
+}
+
+/// テンプレートに渡す、ProjectOutput 以外の情報
+#[derive(Clone, Debug, Default)]
+pub struct RenderContext {
+    /// テンプレートファイルを探すプロジェクトフォルダ
+    pub project_dir: Option<String>,
+    pub date: String,
+    pub dev_memo: String,
+    /// チャンク分割時のみ
+    pub chunk: Option<ChunkHeader>,
+}
+
+impl RenderContext {
+    pub fn new(project_dir: Option<&str>, dev_memo: &str, now: &DateTime<Local>) -> RenderContext {
+        RenderContext {
+            project_dir: project_dir.map(str::to_string),
+            date: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            dev_memo: dev_memo.to_string(),
+            chunk: None,
+        }
+    }
+}
+
+/// 読み込み結果を指定の形式でテキストにする
+///
+/// テンプレート形式ではプロジェクト名・日時・開発メモが空になるため、`render_with` を使うこと。
+pub fn render(output: &ProjectOutput, format: OutputFormat) -> Result<String, String> {
+    render_with(output, format, &RenderContext::default())
+}
+
+/// 読み込み結果を指定の形式でテキストにする（テンプレート用の情報付き）
+pub fn render_with(
+    output: &ProjectOutput,
+    format: OutputFormat,
+    ctx: &RenderContext,
+) -> Result<String, String> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(output).map_err(|e| format!("JSON変換に失敗: {}", e))
+        }
+        OutputFormat::Markdown => Ok(render_markdown(output)),
+        OutputFormat::Xml => Ok(render_xml(output)),
+        OutputFormat::Template => {
+            let template = load_template(ctx.project_dir.as_deref())?;
+            render_template(&template, output, ctx)
+        }
+    }
+}
+
+/// プロジェクトフォルダの `text-read-template.hbs` を読む（なければ既定のテンプレート）
+pub fn load_template(project_dir: Option<&str>) -> Result<String, String> {
+    let Some(dir) = project_dir else {
+        return Ok(DEFAULT_TEMPLATE.to_string());
+    };
+    let path = Path::new(dir).join(TEMPLATE_FILE);
+    if !path.exists() {
+        return Ok(DEFAULT_TEMPLATE.to_string());
+    }
+    std::fs::read_to_string(&path)
+        .map_err(|e| format!("テンプレート読み込み失敗: {} ({})", e, path.display()))
+}
+
+/// Handlebars テンプレートで出力する
+///
+/// 使える値: `project`, `date`, `llm_note`, `dev_memo`, `tree`, `tree_nodes`, `diffs`, `warnings`, `chunk`
+/// （`part`, `total_parts`, `files`）, `files`（FileInfo の各項目に `index`, `language` を加えたもの）。
+/// 出力はプレーンテキストなので HTML エスケープはしない。
+pub fn render_template(
+    template: &str,
+    output: &ProjectOutput,
+    ctx: &RenderContext,
+) -> Result<String, String> {
+    let mut hb = Handlebars::new();
+    hb.register_escape_fn(no_escape);
+    hb.register_template_string(TEMPLATE_FILE, template)
+        .map_err(|e| format!("テンプレートの構文エラー: {}", e))?;
+
+    let files: Vec<Value> = output
+        .files
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let mut v = serde_json::to_value(f).unwrap_or(Value::Null);
+            if let Value::Object(map) = &mut v {
+                map.insert("index".to_string(), json!(i + 1));
+                map.insert("language".to_string(), json!(language_for_path(&f.file_name)));
+            }
+            v
+        })
+        .collect();
+
+    let data = json!({
+        "project": project_name(ctx.project_dir.as_deref()),
+        "date": ctx.date,
+        "llm_note": output.llm_note.as_deref().map(str::trim).filter(|n| !n.is_empty()),
+        "dev_memo": Some(ctx.dev_memo.trim()).filter(|m| !m.is_empty()),
+        "tree": output.tree_view.as_deref().filter(|t| !t.is_empty()),
+        "tree_nodes": output.tree,
+        "files": files,
+        "diffs": output.diffs,
+        "warnings": output.warnings,
+        "chunk": ctx.chunk,
+    });
+
+    hb.render(TEMPLATE_FILE, &data)
+        .map_err(|e| format!("テンプレートの出力エラー: {}", e))
+}
+
+/// Markdown 形式
+///
+/// 先頭に LLM補足、続いてツリーをコードブロックで、最後にファイルごとに
+/// `### パス` の見出しと拡張子から推定した言語タグ付きのコードブロックを並べる。
+/// git の差分があれば `## 差分` に `diff` のコードブロックで続ける。
+pub fn render_markdown(output: &ProjectOutput) -> String {
+    let mut md = String::new();
+
+    if let Some(note) = output.llm_note.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+        md.push_str(note);
+        md.push_str("\n\n");
+    }
+
+    if let Some(tree) = output.tree_view.as_deref().filter(|t| !t.is_empty()) {
+        md.push_str("## ディレクトリツリー\n\n");
+        push_fenced(&mut md, "text", tree);
+        md.push('\n');
+    }
+
+    md.push_str("## ファイル\n");
+    for file in &output.files {
+        md.push_str("\n### ");
+        md.push_str(&file_heading(file));
+        md.push_str("\n\n");
+        push_fenced(&mut md, language_for_path(&file.file_name), &file.file_content);
+    }
+
+    if !output.diffs.is_empty() {
+        md.push_str("\n## 差分\n");
+        for d in &output.diffs {
+            md.push_str(&format!("\n### {} ({})\n\n", d.file_name, d.status));
+            push_fenced(&mut md, "diff", &d.diff);
+        }
+    }
+
+    if !output.warnings.is_empty() {
+        md.push_str("\n## 警告\n\n");
+        for w in &output.warnings {
+            md.push_str(&format!("- {}: {}\n", w.file_name, w.reason));
+        }
+    }
+
+    md
+}
+
+/// 見出し用のファイル名（チャンク分割されたものは行範囲付き）
+pub fn file_heading(file: &FileInfo) -> String {
+    match file.line_range {
+        Some([start, end]) => format!("{} (lines {}-{})", file.file_name, start, end),
+        None => file.file_name.clone(),
+    }
+}
+
+/// コードブロックを追加する
+///
+/// 内容にバッククォートの連続が含まれていても閉じないよう、それより長いフェンスを使う。
+pub fn push_fenced(md: &mut String, lang: &str, content: &str) {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+
+    md.push_str(&fence);
+    md.push_str(lang);
+    md.push('\n');
+    md.push_str(content);
+    if !content.ends_with('\n') {
+        md.push('\n');
+    }
+    md.push_str(&fence);
+    md.push('\n');
+}
+
+/// XML 形式
+///
+/// LLM補足は `<instructions>`、ツリーは `<directory_tree>`、各ファイルは
+/// `<document index="1"><source>パス</source><document_content>内容</document_content></document>`
+/// として `<documents>` の中に並べる。git の差分は `<diffs>` の中に `<diff source="パス" status="modified">` で並べる。
+pub fn render_xml(output: &ProjectOutput) -> String {
+    let mut xml = String::new();
+
+    if let Some(note) = output.llm_note.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+        xml.push_str("<instructions>\n");
+        push_xml_text(&mut xml, note);
+        xml.push_str("\n</instructions>\n");
+    }
+
+    if let Some(tree) = output.tree_view.as_deref().filter(|t| !t.is_empty()) {
+        xml.push_str("<directory_tree>\n");
+        push_xml_text(&mut xml, tree.trim_end_matches('\n'));
+        xml.push_str("\n</directory_tree>\n");
+    }
+
+    xml.push_str("<documents>\n");
+    for (i, file) in output.files.iter().enumerate() {
+        xml.push_str(&format!("<document index=\"{}\"", i + 1));
+        if let Some([start, end]) = file.line_range {
+            xml.push_str(&format!(" lines=\"{}-{}\"", start, end));
+        }
+        xml.push_str(">\n<source>");
+        xml.push_str(&escape_xml(&file.file_name));
+        xml.push_str("</source>\n<document_content>\n");
+        push_xml_text(&mut xml, file.file_content.trim_end_matches('\n'));
+        xml.push_str("\n</document_content>\n</document>\n");
+    }
+    xml.push_str("</documents>\n");
+
+    if !output.diffs.is_empty() {
+        xml.push_str("<diffs>\n");
+        for d in &output.diffs {
+            xml.push_str(&format!(
+                "<diff source=\"{}\" status=\"{}\">\n",
+                escape_xml(&d.file_name),
+                escape_xml(&d.status)
+            ));
+            push_xml_text(&mut xml, &d.diff);
+            xml.push_str("\n</diff>\n");
+        }
+        xml.push_str("</diffs>\n");
+    }
+
+    if !output.warnings.is_empty() {
+        xml.push_str("<warnings>\n");
+        for w in &output.warnings {
+            xml.push_str(&format!(
+                "<warning source=\"{}\">{}</warning>\n",
+                escape_xml(&w.file_name),
+                escape_xml(&w.reason)
+            ));
+        }
+        xml.push_str("</warnings>\n");
+    }
+
+    xml
+}
+
+/// 要素の本文を追加する
+///
+/// `<` や `&` を含むソースコードは実体参照だらけになると読みにくいため CDATA で囲む。
+/// 内容に `]]>` があれば CDATA を分割し、XML で使えない制御文字は U+FFFD に置き換える。
+fn push_xml_text(xml: &mut String, text: &str) {
+    let text = replace_invalid_xml_chars(text);
+    if !text.contains(['<', '&', '>']) {
+        xml.push_str(&text);
+        return;
+    }
+    xml.push_str("<![CDATA[");
+    xml.push_str(&text.replace("]]>", "]]]]><![CDATA[>"));
+    xml.push_str("]]>");
+}
+
+/// 属性値・短い本文用のエスケープ
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in replace_invalid_xml_chars(text).chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// XML 1.0 で使えない文字（タブ・改行以外の制御文字など）を U+FFFD に置き換える
+fn replace_invalid_xml_chars(text: &str) -> std::borrow::Cow<'_, str> {
+    let valid = |c: char| {
+        matches!(c, '\t' | '\n' | '\r')
+            || ('\u{20}'..='\u{D7FF}').contains(&c)
+            || ('\u{E000}'..='\u{FFFD}').contains(&c)
+            || c >= '\u{10000}'
+    };
+    if text.chars().all(valid) {
+        std::borrow::Cow::Borrowed(text)
+    } else {
+        std::borrow::Cow::Owned(text.chars().map(|c| if valid(c) { c } else { '\u{FFFD}' }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FileDiff, FileWarning};
+
+    fn file(name: &str, content: &str) -> FileInfo {
+        FileInfo {
+            file_url: format!("/proj/{}", name),
+            file_name: name.to_string(),
+            file_content: content.to_string(),
+            ..FileInfo::default()
+        }
+    }
+
+    fn output(files: Vec<FileInfo>) -> ProjectOutput {
+        ProjectOutput {
+            llm_note: None,
+            files,
+            tree_view: None,
+            tree: None,
+            diffs: Vec::new(),
+            warnings: Vec::new(),
+            compaction: None,
+            redactions: Vec::new(),
+        }
+    }
+
+    fn fenced(lang: &str, content: &str) -> String {
+        let mut md = String::new();
+        push_fenced(&mut md, lang, content);
+        md
+    }
+
+    #[test]
+    fn fence_is_longer_than_backticks_in_the_content() {
+        assert_eq!(fenced("rust", "fn a() {}"), "```rust\nfn a() {}\n```\n");
+        assert_eq!(fenced("markdown", "```rust\nx\n```\n"), "````markdown\n```rust\nx\n```\n````\n");
+        assert_eq!(fenced("", "a ````` b"), "``````\na ````` b\n``````\n");
+        assert_eq!(fenced("text", "`code`\n"), "```text\n`code`\n```\n");
+    }
+
+    #[test]
+    fn markdown_lists_files_with_language_tags() {
+        let mut out = output(vec![file("src/main.rs", "fn main() {}\n"), file("notes", "memo")]);
+        out.llm_note = Some("  レビューしてください \n".to_string());
+        out.tree_view = Some("proj\n└── src\n".to_string());
+        out.files[1].line_range = Some([10, 20]);
+        out.diffs.push(FileDiff {
+            file_name: "src/main.rs".to_string(),
+            status: "modified".to_string(),
+            diff: "@@ -1 +1 @@\n-a\n+b".to_string(),
+        });
+        out.warnings.push(FileWarning {
+            file_name: "big.bin".to_string(),
+            reason: "バイナリ".to_string(),
+        });
+        assert_eq!(
+            render_markdown(&out),
+            "レビューしてください\n\n\
+             ## ディレクトリツリー\n\n```text\nproj\n└── src\n```\n\n\
+             ## ファイル\n\n### src/main.rs\n\n```rust\nfn main() {}\n```\n\
+             \n### notes (lines 10-20)\n\n```\nmemo\n```\n\
+             \n## 差分\n\n### src/main.rs (modified)\n\n```diff\n@@ -1 +1 @@\n-a\n+b\n```\n\
+             \n## 警告\n\n- big.bin: バイナリ\n"
+        );
+    }
+
+    #[test]
+    fn markdown_skips_empty_sections() {
+        let mut out = output(vec![file("a.py", "print(1)")]);
+        out.llm_note = Some("  ".to_string());
+        out.tree_view = Some(String::new());
+        assert_eq!(render_markdown(&out), "## ファイル\n\n### a.py\n\n```python\nprint(1)\n```\n");
+    }
+
+    fn xml_text(text: &str) -> String {
+        let mut xml = String::new();
+        push_xml_text(&mut xml, text);
+        xml
+    }
+
+    #[test]
+    fn plain_text_is_not_wrapped_in_cdata() {
+        assert_eq!(xml_text("let a = 1;"), "let a = 1;");
+        assert_eq!(xml_text("if a < b && c {}"), "<![CDATA[if a < b && c {}]]>");
+    }
+
+    #[test]
+    fn cdata_end_marker_is_split() {
+        let text = "let x = a[b[0]]>c;";
+        let xml = xml_text(text);
+        assert_eq!(xml, "<![CDATA[let x = a[b[0]]]]><![CDATA[>c;]]>");
+        // CDATA を外して繋げると元に戻る
+        let restored: String = xml
+            .split("<![CDATA[")
+            .filter(|s| !s.is_empty())
+            .map(|s| s.strip_suffix("]]>").unwrap())
+            .collect();
+        assert_eq!(restored, text);
+        assert_eq!(xml_text("]]>]]>").matches("<![CDATA[").count(), 3);
+    }
+
+    #[test]
+    fn invalid_xml_chars_are_replaced() {
+        assert_eq!(xml_text("a\u{0}b\u{1b}[0m\tc"), "a\u{FFFD}b\u{FFFD}[0m\tc");
+        assert_eq!(escape_xml("<a href=\"x\">'&'</a>"), "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;");
+        assert_eq!(escape_xml("bell\u{7}"), "bell\u{FFFD}");
+    }
+
+    #[test]
+    fn xml_wraps_each_file_in_a_document() {
+        let mut out = output(vec![file("src/a&b.rs", "x < y\n"), file("b.txt", "plain")]);
+        out.llm_note = Some("補足".to_string());
+        out.files[1].line_range = Some([1, 5]);
+        out.diffs.push(FileDiff {
+            file_name: "b.txt".to_string(),
+            status: "added".to_string(),
+            diff: "+plain".to_string(),
+        });
+        assert_eq!(
+            render_xml(&out),
+            "<instructions>\n補足\n</instructions>\n<documents>\n\
+             <document index=\"1\">\n<source>src/a&amp;b.rs</source>\n<document_content>\n<![CDATA[x < y]]>\n</document_content>\n</document>\n\
+             <document index=\"2\" lines=\"1-5\">\n<source>b.txt</source>\n<document_content>\nplain\n</document_content>\n</document>\n\
+             </documents>\n<diffs>\n<diff source=\"b.txt\" status=\"added\">\n+plain\n</diff>\n</diffs>\n"
+        );
+    }
+
+    fn ctx() -> RenderContext {
+        RenderContext {
+            project_dir: Some("/work/my-proj".to_string()),
+            date: "2026-01-02 03:04:05".to_string(),
+            dev_memo: " メモ ".to_string(),
+            chunk: None,
+        }
+    }
+
+    #[test]
+    fn template_sees_project_files_and_context() {
+        let mut out = output(vec![file("src/main.rs", "fn main() {}"), file("a.txt", "<a & b>")]);
+        out.files[0].encoding = Some("UTF-8".to_string());
+        let template = "{{project}} {{date}} [{{dev_memo}}]{{#if llm_note}} note{{/if}}\n\
+                        {{#each files}}{{index}}:{{file_name}}:{{language}}:{{encoding}}:{{file_content}}\n{{/each}}";
+        assert_eq!(
+            render_template(template, &out, &ctx()).unwrap(),
+            "my-proj 2026-01-02 03:04:05 [メモ]\n1:src/main.rs:rust:UTF-8:fn main() {}\n2:a.txt:text::<a & b>\n"
+        );
+    }
+
+    #[test]
+    fn default_template_matches_the_text_export() {
+        let mut out = output(vec![file("a.rs", "x")]);
+        out.llm_note = Some("補足".to_string());
+        assert_eq!(
+            render_template(DEFAULT_TEMPLATE, &out, &ctx()).unwrap(),
+            "// LLM補足:\n補足\n\n// Project: my-proj, Date: 2026-01-02 03:04:05\nFile: a.rs\nx\n--------------------------------\n// End of chunk.\n"
+        );
+    }
+
+    #[test]
+    fn template_errors_are_reported() {
+        let out = output(Vec::new());
+        let err = render_template("{{#each files}}", &out, &ctx()).unwrap_err();
+        assert!(err.starts_with("テンプレートの構文エラー"), "{}", err);
+        let err = render_template("{{no_such_helper files}}", &out, &ctx()).unwrap_err();
+        assert!(err.starts_with("テンプレートの出力エラー"), "{}", err);
+    }
+
+    #[test]
+    fn template_file_is_read_from_the_project() {
+        let dir = std::env::temp_dir().join(format!("text-read-template-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir_str = dir.to_string_lossy().to_string();
+        assert_eq!(load_template(Some(&dir_str)).unwrap(), DEFAULT_TEMPLATE);
+        std::fs::write(dir.join(TEMPLATE_FILE), "{{#each files}}{{file_name}}{{/each}}").unwrap();
+        let ctx = RenderContext {
+            project_dir: Some(dir_str.clone()),
+            ..RenderContext::default()
+        };
+        let rendered = render_with(&output(vec![file("a.rs", "")]), OutputFormat::Template, &ctx);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(rendered.unwrap(), "a.rs");
+        assert_eq!(load_template(None).unwrap(), DEFAULT_TEMPLATE);
+    }
+}
@@ -4,7 +4,43 @@ use std::{
     path::Path,
 };
 
-use crate::models::LoadedSettings;
+use crate::fileops::{check_compact_rule, check_encoding_override, check_pattern, check_regex};
+use crate::git::{DiffContent, DiffSpec, GitSelection};
+use crate::limits::OversizeAction;
+use crate::metadata::MetadataFields;
+use crate::order::FileOrder;
+use crate::patterns::GLOB_PREFIX;
+use crate::render::OutputFormat;
+use crate::chunk::ChunkUnit;
+use crate::tokens::TokenEncoding;
+use crate::models::{CollectOptions, CompactRule, EncodingOverride, LoadedSettings, PatternError, PatternKind};
+
+/// "true"/"false" 等の設定値を bool として解釈する
+fn parse_bool(val: &str) -> bool {
+    matches!(val.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes" | "on")
+}
+
+/// `KEY=値` の値がどの選択肢にも当たらないときのエラー
+fn invalid_value(line: usize, line_text: &str, valid: &str) -> PatternError {
+    PatternError {
+        kind: PatternKind::Setting,
+        line,
+        pattern: line_text.to_string(),
+        message: format!("不明な値です。{} のいずれかを指定してください", valid),
+    }
+}
+
+/// 上限・予算等の数値の設定値を解釈する（空欄と `0` は指定なし）
+fn parse_limit(val: &str) -> Result<Option<usize>, std::num::ParseIntError> {
+    let val = val.trim();
+    if val.is_empty() {
+        return Ok(None);
+    }
+    val.parse().map(|n: usize| Some(n).filter(|&n| n > 0))
+}
+
+/// 数値の設定値の選択肢（`invalid_value` 用）
+const LIMIT_VALUES: &str = "0（無制限） / 1 以上の整数";
 
 /// 設定ファイルの読み込み
 pub fn load_settings(base_dir: &str) -> LoadedSettings {
@@ -14,10 +50,13 @@ pub fn load_settings(base_dir: &str) -> LoadedSettings {
     let mut output_path: Option<String> = None;
     let mut dev_memo = Vec::new();
     let mut llm_note = Vec::new(); // ← LLM補足
+    let mut options = CollectOptions::default();
+    let mut pattern_errors = Vec::new();
 
     if settings_path.exists() {
         if let Ok(file) = File::open(settings_path) {
-            for line in BufReader::new(file).lines() {
+            for (idx, line) in BufReader::new(file).lines().enumerate() {
+                let line_no = idx + 1;
                 if let Ok(raw_line) = line {
                     let trimmed = raw_line.trim();
                     if trimmed.is_empty() || trimmed.starts_with('#') {
@@ -29,9 +68,213 @@ pub fn load_settings(base_dir: &str) -> LoadedSettings {
                             output_path = Some(val.to_string());
                         }
                     }
+                    else if let Some(rest) = trimmed.strip_prefix("RESPECT_IGNORE=") {
+                        options.respect_ignore = parse_bool(rest);
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("TOKEN_ENCODING=") {
+                        match TokenEncoding::from_name(rest) {
+                            Some(enc) => options.token_encoding = enc,
+                            None => pattern_errors.push(invalid_value(line_no, trimmed, "cl100k_base / o200k_base / p50k_base / claude")),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("TOKEN_BUDGET=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.token_budget = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("OUTPUT_FORMAT=") {
+                        match OutputFormat::from_name(rest) {
+                            Some(format) => options.output_format = format,
+                            None => pattern_errors.push(invalid_value(line_no, trimmed, "json / markdown / xml / template")),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("LINE_NUMBERS=") {
+                        options.line_numbers = parse_bool(rest);
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("METADATA=") {
+                        match MetadataFields::from_list(rest) {
+                            Ok(fields) => options.metadata = fields,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, "size / lines / modified / language / sha256 / tokens / encoding / all / none")),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("ORDER=") {
+                        match FileOrder::from_name(rest) {
+                            Some(order) => options.order = order,
+                            None => pattern_errors.push(invalid_value(line_no, trimmed, "path / pattern / size / modified")),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("MAX_FILE_BYTES=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.limits.max_file_bytes = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("MAX_FILE_LINES=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.limits.max_file_lines = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("MAX_TOTAL_BYTES=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.limits.max_total_bytes = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("MAX_TOTAL_TOKENS=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.limits.max_total_tokens = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("OVERSIZE=") {
+                        match OversizeAction::from_name(rest) {
+                            Some(action) => options.limits.oversize = action,
+                            None => pattern_errors.push(invalid_value(line_no, trimmed, "truncate / skip")),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("GIT_CHANGED=") {
+                        match GitSelection::from_setting(rest) {
+                            Some(selection) => options.git_selection = selection,
+                            None => pattern_errors.push(invalid_value(line_no, trimmed, "off / working-tree / since:<ref> / merge-base:<ref>")),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("DIFF=") {
+                        // ref の誤りは収集時ではなくここで行番号付きで知らせる
+                        let spec = DiffSpec::from_setting(rest);
+                        if let Some(Err(e)) = spec.as_ref().map(|s| s.verify(base_dir)) {
+                            pattern_errors.push(PatternError {
+                                kind: PatternKind::Setting,
+                                line: line_no,
+                                pattern: trimmed.to_string(),
+                                message: e,
+                            });
+                        }
+                        options.diff = spec;
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("DIFF_CONTENT=") {
+                        match DiffContent::from_name(rest) {
+                            Some(content) => options.diff_content = content,
+                            None => pattern_errors.push(invalid_value(line_no, trimmed, "all / changed / none")),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("TREE_MAX_DEPTH=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.tree_limits.max_depth = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("TREE_MAX_ENTRIES=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.tree_limits.max_entries = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("TREE_COLLAPSE=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.tree_limits.collapse_files = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("TREE_INCLUDED_ONLY=") {
+                        options.tree_limits.included_only = parse_bool(rest);
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("REDACT_SECRETS=") {
+                        options.keep_secrets = !parse_bool(rest);
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("CHUNK_LIMIT=") {
+                        match parse_limit(rest) {
+                            Ok(limit) => options.chunk_limit = limit,
+                            Err(_) => pattern_errors.push(invalid_value(line_no, trimmed, LIMIT_VALUES)),
+                        }
+                    }
+                    else if let Some(rest) = trimmed.strip_prefix("CHUNK_UNIT=") {
+                        match ChunkUnit::from_name(rest) {
+                            Some(unit) => options.chunk_unit = unit,
+                            None => pattern_errors.push(invalid_value(line_no, trimmed, "bytes / tokens")),
+                        }
+                    }
+                    // ENCODING:<エンコーディング名>:<正規表現>
+                    else if let Some(rest) = trimmed.strip_prefix("ENCODING:") {
+                        if let Some((enc, pat)) = rest.split_once(':') {
+                            if !enc.trim().is_empty() && !pat.trim().is_empty() {
+                                pattern_errors.extend(check_encoding_override(line_no, enc, pat));
+                                options.encoding_overrides.push(EncodingOverride {
+                                    encoding: enc.trim().to_string(),
+                                    pattern: pat.trim().to_string(),
+                                });
+                            }
+                        }
+                    }
+                    // COMPACT:<種類,...>:<正規表現>
+                    else if let Some(rest) = trimmed.strip_prefix("COMPACT:") {
+                        if let Some((modes, pat)) = rest.split_once(':') {
+                            if !modes.trim().is_empty() && !pat.trim().is_empty() {
+                                pattern_errors.extend(check_compact_rule(line_no, modes, pat));
+                                options.compact_rules.push(CompactRule {
+                                    modes: modes.trim().to_string(),
+                                    pattern: pat.trim().to_string(),
+                                });
+                            }
+                        }
+                    }
+                    // OUTLINE:<正規表現>
+                    else if let Some(rest) = trimmed.strip_prefix("OUTLINE:") {
+                        let val = rest.trim();
+                        if !val.is_empty() {
+                            pattern_errors.extend(check_regex(PatternKind::Outline, line_no, val));
+                            options.outline_patterns.push(val.to_string());
+                        }
+                    }
+                    // REDACT:<正規表現>
+                    else if let Some(rest) = trimmed.strip_prefix("REDACT:") {
+                        let val = rest.trim();
+                        if !val.is_empty() {
+                            pattern_errors.extend(check_regex(PatternKind::Redact, line_no, val));
+                            options.redact_patterns.push(val.to_string());
+                        }
+                    }
+                    // PIN:<グロブ>
+                    else if let Some(rest) = trimmed.strip_prefix("PIN:") {
+                        let val = rest.trim();
+                        if !val.is_empty() {
+                            let pat = format!("{}{}", GLOB_PREFIX, val);
+                            pattern_errors.extend(check_pattern(PatternKind::Pin, line_no, &pat));
+                            options.pinned.push(val.to_string());
+                        }
+                    }
+                    // DENY_OVERRIDE:<グロブ>
+                    else if let Some(rest) = trimmed.strip_prefix("DENY_OVERRIDE:") {
+                        let val = rest.trim();
+                        if !val.is_empty() {
+                            let pat = format!("{}{}", GLOB_PREFIX, val);
+                            pattern_errors.extend(check_pattern(PatternKind::Deny, line_no, &pat));
+                            options.deny_overrides.push(val.to_string());
+                        }
+                    }
+                    // DENY:<グロブ>
+                    else if let Some(rest) = trimmed.strip_prefix("DENY:") {
+                        let val = rest.trim();
+                        if !val.is_empty() {
+                            let pat = format!("{}{}", GLOB_PREFIX, val);
+                            pattern_errors.extend(check_pattern(PatternKind::Deny, line_no, &pat));
+                            options.deny_patterns.push(val.to_string());
+                        }
+                    }
+                    // EXCLUDE_GLOB:<グロブ> は GUI の Exclude 欄では "GLOB:<グロブ>" として扱う
+                    else if let Some(rest) = trimmed.strip_prefix("EXCLUDE_GLOB:") {
+                        let val = rest.trim();
+                        if !val.is_empty() {
+                            let pat = format!("{}{}", GLOB_PREFIX, val);
+                            pattern_errors.extend(check_pattern(PatternKind::Exclude, line_no, &pat));
+                            patterns_exclude.push(pat);
+                        }
+                    }
                     else if let Some(rest) = trimmed.strip_prefix("EXCLUDE:") {
                         let val = rest.trim();
                         if !val.is_empty() {
+                            pattern_errors.extend(check_pattern(PatternKind::Exclude, line_no, val));
                             patterns_exclude.push(val.to_string());
                         }
                     }
@@ -44,6 +287,7 @@ pub fn load_settings(base_dir: &str) -> LoadedSettings {
                     }
                     else {
                         // 上記以外はinclude扱い
+                        pattern_errors.extend(check_pattern(PatternKind::Include, line_no, trimmed));
                         patterns_include.push(trimmed.to_string());
                     }
                 }
@@ -57,9 +301,106 @@ pub fn load_settings(base_dir: &str) -> LoadedSettings {
         output_path,
         dev_memo,
         llm_note,
+        options,
+        pattern_errors,
     }
 }
 
+/// 収集オプションを設定ファイルの行に変換する（既定値のものは書かない）
+fn option_lines(options: &CollectOptions) -> Vec<String> {
+    let mut lines = Vec::new();
+    if options.respect_ignore {
+        lines.push("RESPECT_IGNORE=true".to_string());
+    }
+    if options.token_encoding != TokenEncoding::default() {
+        lines.push(format!("TOKEN_ENCODING={}", options.token_encoding.name()));
+    }
+    if let Some(budget) = options.token_budget {
+        lines.push(format!("TOKEN_BUDGET={}", budget));
+    }
+    if options.output_format != OutputFormat::default() {
+        lines.push(format!("OUTPUT_FORMAT={}", options.output_format.name()));
+    }
+    if options.line_numbers {
+        lines.push("LINE_NUMBERS=true".to_string());
+    }
+    if options.metadata != MetadataFields::default() {
+        lines.push(format!("METADATA={}", options.metadata.to_list()));
+    }
+    if options.order != FileOrder::default() {
+        lines.push(format!("ORDER={}", options.order.name()));
+    }
+    let limits = &options.limits;
+    if let Some(max) = limits.max_file_bytes {
+        lines.push(format!("MAX_FILE_BYTES={}", max));
+    }
+    if let Some(max) = limits.max_file_lines {
+        lines.push(format!("MAX_FILE_LINES={}", max));
+    }
+    if let Some(max) = limits.max_total_bytes {
+        lines.push(format!("MAX_TOTAL_BYTES={}", max));
+    }
+    if let Some(max) = limits.max_total_tokens {
+        lines.push(format!("MAX_TOTAL_TOKENS={}", max));
+    }
+    if limits.oversize != OversizeAction::default() {
+        lines.push(format!("OVERSIZE={}", limits.oversize.name()));
+    }
+    if !options.git_selection.is_off() {
+        lines.push(format!("GIT_CHANGED={}", options.git_selection.to_setting()));
+    }
+    if let Some(spec) = &options.diff {
+        lines.push(format!("DIFF={}", spec.to_setting()));
+    }
+    if options.diff_content != DiffContent::default() {
+        lines.push(format!("DIFF_CONTENT={}", options.diff_content.name()));
+    }
+    let tree = &options.tree_limits;
+    if let Some(max) = tree.max_depth {
+        lines.push(format!("TREE_MAX_DEPTH={}", max));
+    }
+    if let Some(max) = tree.max_entries {
+        lines.push(format!("TREE_MAX_ENTRIES={}", max));
+    }
+    if let Some(max) = tree.collapse_files {
+        lines.push(format!("TREE_COLLAPSE={}", max));
+    }
+    if tree.included_only {
+        lines.push("TREE_INCLUDED_ONLY=true".to_string());
+    }
+    if options.keep_secrets {
+        lines.push("REDACT_SECRETS=false".to_string());
+    }
+    if let Some(limit) = options.chunk_limit {
+        lines.push(format!("CHUNK_LIMIT={}", limit));
+    }
+    if options.chunk_unit != ChunkUnit::default() {
+        lines.push(format!("CHUNK_UNIT={}", options.chunk_unit.name()));
+    }
+    for ov in &options.encoding_overrides {
+        lines.push(format!("ENCODING:{}:{}", ov.encoding, ov.pattern));
+    }
+    for rule in &options.compact_rules {
+        lines.push(format!("COMPACT:{}:{}", rule.modes, rule.pattern));
+    }
+    for pat in &options.outline_patterns {
+        lines.push(format!("OUTLINE:{}", pat));
+    }
+    for pat in &options.redact_patterns {
+        lines.push(format!("REDACT:{}", pat));
+    }
+    for pat in &options.pinned {
+        lines.push(format!("PIN:{}", pat));
+    }
+    for pat in &options.deny_patterns {
+        lines.push(format!("DENY:{}", pat));
+    }
+    for pat in &options.deny_overrides {
+        lines.push(format!("DENY_OVERRIDE:{}", pat));
+    }
+    lines
+}
+
 /// 設定ファイルの書き込み
 pub fn write_settings(
     project_dir: &str,
@@ -68,6 +409,7 @@ pub fn write_settings(
     output_path: &Option<String>,
     dev_memo: &[&str],
     llm_note: &[&str], // ← LLM補足
+    options: &CollectOptions,
 ) -> Result<(), String> {
     let settings_path = Path::new(project_dir).join("text-read-settings.txt");
 
@@ -81,6 +423,13 @@ pub fn write_settings(
             .map_err(|e| format!("OUTPUT_PATH書き込み失敗: {}", e))?;
     }
 
+    // 1.5) 収集オプション（既定値と異なるもののみ）
+    let opt_lines = option_lines(options);
+    if !opt_lines.is_empty() {
+        writeln!(file, "{}\n", opt_lines.join("\n"))
+            .map_err(|e| format!("オプション書き込み失敗: {}", e))?;
+    }
+
     // 2) includeパターン
     for pat in include_patterns {
         if let Err(e) = writeln!(file, "{}", pat) {
@@ -88,9 +437,13 @@ pub fn write_settings(
         }
     }
 
-    // 3) excludeパターン（行頭に "EXCLUDE:" を付加）
+    // 3) excludeパターン（行頭に "EXCLUDE:"、グロブは "EXCLUDE_GLOB:" を付加）
     for pat in exclude_patterns {
-        if let Err(e) = writeln!(file, "EXCLUDE:{}", pat) {
+        let line = match pat.strip_prefix(GLOB_PREFIX) {
+            Some(glob) => format!("EXCLUDE_GLOB:{}", glob),
+            None => format!("EXCLUDE:{}", pat),
+        };
+        if let Err(e) = writeln!(file, "{}", line) {
             return Err(format!("Excludeパターン書き込みに失敗: {}", e));
         }
     }
@@ -111,3 +464,90 @@ pub fn write_settings(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 設定ファイルを一時フォルダに書いて読み込む
+    fn load(name: &str, content: &str) -> LoadedSettings {
+        let dir = std::env::temp_dir().join(format!("text-read-settings-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("text-read-settings.txt"), content).unwrap();
+        let loaded = load_settings(&dir.to_string_lossy());
+        let _ = std::fs::remove_dir_all(&dir);
+        loaded
+    }
+
+    fn error_lines(loaded: &LoadedSettings) -> Vec<usize> {
+        loaded.pattern_errors.iter().map(|e| e.line).collect()
+    }
+
+    #[test]
+    fn reads_glob_patterns() {
+        let loaded = load("glob", "GLOB:src/**/*.rs\nEXCLUDE_GLOB:target/\nEXCLUDE:\\.lock$\nGLOB:src/[\n");
+        assert_eq!(loaded.patterns_include, ["GLOB:src/**/*.rs", "GLOB:src/["]);
+        assert_eq!(loaded.patterns_exclude, ["GLOB:target/", "\\.lock$"]);
+        // 壊れたグロブは行番号付きで報告する
+        assert_eq!(error_lines(&loaded), vec![4]);
+        assert_eq!(loaded.pattern_errors[0].kind, PatternKind::Include);
+    }
+
+    #[test]
+    fn parses_limits() {
+        assert_eq!(parse_limit(" 1000 "), Ok(Some(1000)));
+        assert_eq!(parse_limit("0"), Ok(None));
+        assert_eq!(parse_limit(""), Ok(None));
+        assert!(parse_limit("10k").is_err());
+        assert!(parse_limit("-5").is_err());
+    }
+
+    #[test]
+    fn reports_invalid_size_limits() {
+        let loaded = load(
+            "limits",
+            "MAX_FILE_BYTES=10k\nMAX_FILE_LINES=200\nMAX_TOTAL_BYTES=-5\nMAX_TOTAL_TOKENS=0\n",
+        );
+        assert_eq!(error_lines(&loaded), vec![1, 3]);
+        assert_eq!(loaded.pattern_errors[0].kind, PatternKind::Setting);
+        assert_eq!(loaded.pattern_errors[0].pattern, "MAX_FILE_BYTES=10k");
+        assert_eq!(loaded.options.limits.max_file_bytes, None);
+        assert_eq!(loaded.options.limits.max_file_lines, Some(200));
+        assert_eq!(loaded.options.limits.max_total_tokens, None);
+    }
+
+    #[test]
+    fn reports_invalid_token_budget() {
+        let loaded = load("budget", "TOKEN_BUDGET=100000\n");
+        assert!(loaded.pattern_errors.is_empty());
+        assert_eq!(loaded.options.token_budget, Some(100_000));
+        let loaded = load("budget-typo", "# budget\nTOKEN_BUDGET=100,000\n");
+        assert_eq!(error_lines(&loaded), vec![2]);
+        assert_eq!(loaded.options.token_budget, None);
+    }
+
+    #[test]
+    fn reports_invalid_chunk_limit() {
+        let loaded = load("chunk", "CHUNK_UNIT=tokens\nCHUNK_LIMIT=12k\n");
+        assert_eq!(error_lines(&loaded), vec![2]);
+        assert_eq!(loaded.options.chunk_limit, None);
+        assert_eq!(load("chunk-ok", "CHUNK_LIMIT=8000\n").options.chunk_limit, Some(8000));
+    }
+
+    #[test]
+    fn reports_invalid_tree_limits() {
+        let loaded = load("tree", "TREE_MAX_DEPTH=2\nTREE_MAX_ENTRIES=many\nTREE_COLLAPSE=1.5\n");
+        assert_eq!(error_lines(&loaded), vec![2, 3]);
+        assert_eq!(loaded.options.tree_limits.max_depth, Some(2));
+        assert_eq!(loaded.options.tree_limits.max_entries, None);
+        assert_eq!(loaded.options.tree_limits.collapse_files, None);
+    }
+
+    #[test]
+    fn reports_unresolvable_diff_refs() {
+        // 一時フォルダは git リポジトリではないので ref は解決できない
+        let loaded = load("diff", "DIFF=off\nDIFF=no-such-branch\n");
+        assert_eq!(error_lines(&loaded), vec![2]);
+        assert!(loaded.pattern_errors[0].message.contains("no-such-branch"));
+    }
+}
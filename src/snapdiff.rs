@@ -0,0 +1,434 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use similar::TextDiff;
+
+use crate::encoding::{decode_guess, decode_with_bom};
+use crate::fileops::is_binary;
+use crate::models::{CollectOptions, FileInfo};
+use crate::redact::Redactor;
+use crate::render::push_fenced;
+
+/// 比較する一方
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotSource {
+    /// `target/backup/` 直下のスナップショットのフォルダ名
+    Snapshot(String),
+    /// プロジェクトの現在の内容（include・exclude で選んだファイル）
+    Live,
+}
+
+impl SnapshotSource {
+    /// 表示名
+    pub fn label(&self) -> String {
+        match self {
+            SnapshotSource::Snapshot(name) => name.clone(),
+            SnapshotSource::Live => "現在のファイル".to_string(),
+        }
+    }
+}
+
+/// ファイルの変化の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl ChangeKind {
+    /// 一覧に付ける記号（git の `--name-status` と同じ）
+    pub fn mark(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "A",
+            ChangeKind::Removed => "D",
+            ChangeKind::Modified => "M",
+        }
+    }
+}
+
+/// 1 ファイル分の変化
+#[derive(Clone, Debug, Serialize)]
+pub struct FileChange {
+    pub file_name: String,
+    pub kind: ChangeKind,
+    /// unified diff（バイナリファイルはその旨の 1 行）
+    pub diff: String,
+}
+
+/// 2 つのスナップショット（またはスナップショットと現在のファイル）の比較結果
+#[derive(Clone, Debug, Serialize)]
+pub struct SnapshotDiff {
+    pub old: String,
+    pub new: String,
+    /// パス順
+    pub changes: Vec<FileChange>,
+    /// 変化のなかったファイル数
+    pub unchanged: usize,
+}
+
+impl SnapshotDiff {
+    fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+
+    /// "追加 1 / 削除 0 / 変更 2 / 変更なし 10"
+    pub fn summary(&self) -> String {
+        format!(
+            "追加 {} / 削除 {} / 変更 {} / 変更なし {}",
+            self.count(ChangeKind::Added),
+            self.count(ChangeKind::Removed),
+            self.count(ChangeKind::Modified),
+            self.unchanged
+        )
+    }
+
+    /// "M src/app.rs" のような変化のあったファイルの一覧
+    pub fn file_list(&self) -> String {
+        self.changes
+            .iter()
+            .map(|c| format!("{} {}", c.kind.mark(), c.file_name))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// LLM にそのまま貼り付けられるテキスト（見出し・一覧・`diff` のコードブロック）
+    pub fn to_text(&self) -> String {
+        let mut text = format!("// Snapshot diff: {} → {}\n// {}\n", self.old, self.new, self.summary());
+        if self.changes.is_empty() {
+            return text;
+        }
+        text.push('\n');
+        text.push_str(&self.file_list());
+        text.push_str("\n\n");
+        let diffs: Vec<&str> = self.changes.iter().map(|c| c.diff.as_str()).collect();
+        push_fenced(&mut text, "diff", &diffs.join("\n"));
+        text
+    }
+}
+
+/// `target/backup/` 直下のスナップショットのフォルダ名（古い順。名前が日時で始まるため名前順と同じ）
+pub fn list_snapshots(base_dir: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(backup_dir(base_dir)) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+/// CLI 等で指定された名前を解釈する
+///
+/// `live` は現在のファイル、`latest` / `previous` は最新 / 1 つ前のスナップショット。
+/// それ以外はフォルダ名（前方一致で 1 つに決まれば省略可）。
+pub fn resolve_snapshot(base_dir: &str, name: &str) -> Result<SnapshotSource, String> {
+    let name = name.trim();
+    if name.eq_ignore_ascii_case("live") {
+        return Ok(SnapshotSource::Live);
+    }
+    let snapshots = list_snapshots(base_dir);
+    let found = match name.to_ascii_lowercase().as_str() {
+        "latest" => snapshots.last().cloned(),
+        "previous" => snapshots.iter().rev().nth(1).cloned(),
+        _ if snapshots.iter().any(|s| s == name) => Some(name.to_string()),
+        _ => {
+            let matches: Vec<&String> = snapshots.iter().filter(|s| s.starts_with(name)).collect();
+            match matches.as_slice() {
+                [one] => Some(one.to_string()),
+                _ => None,
+            }
+        }
+    };
+    found.map(SnapshotSource::Snapshot).ok_or_else(|| {
+        let list = if snapshots.is_empty() { "（なし）".to_string() } else { snapshots.join(", ") };
+        format!("スナップショットが見つかりません: {} (あるもの: {})", name, list)
+    })
+}
+
+/// 2 つを比較する
+///
+/// 現在のファイル側は `live`（`Collector::selected_files` で選んだファイル）をディスクから読み直して使う。
+/// 秘密情報は `options` の設定どおりに両側とも伏せ字にしてから比べる。
+pub fn compare_snapshots(
+    base_dir: &str,
+    old: &SnapshotSource,
+    new: &SnapshotSource,
+    live: &[FileInfo],
+    options: &CollectOptions,
+) -> Result<SnapshotDiff, String> {
+    let old_files = source_files(base_dir, old, live)?;
+    let new_files = source_files(base_dir, new, live)?;
+    let redactor = Redactor::new(!options.keep_secrets, &options.redact_patterns);
+
+    let mut names: Vec<&String> = old_files.keys().chain(new_files.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+    for name in names {
+        let old_bytes = old_files.get(name).map(fs::read).transpose().map_err(|e| read_error(name, e))?;
+        let new_bytes = new_files.get(name).map(fs::read).transpose().map_err(|e| read_error(name, e))?;
+        let kind = match (&old_bytes, &new_bytes) {
+            (Some(a), Some(b)) if a == b => {
+                unchanged += 1;
+                continue;
+            }
+            (Some(_), Some(_)) => ChangeKind::Modified,
+            (None, _) => ChangeKind::Added,
+            (_, None) => ChangeKind::Removed,
+        };
+
+        let old_label = if old_bytes.is_some() { format!("a/{}", name) } else { "/dev/null".to_string() };
+        let new_label = if new_bytes.is_some() { format!("b/{}", name) } else { "/dev/null".to_string() };
+        let binary = [&old_bytes, &new_bytes].iter().any(|b| b.as_deref().is_some_and(is_binary));
+        let diff = if binary {
+            format!("Binary files {} and {} differ", old_label, new_label)
+        } else {
+            let old_text = decode(name, old_bytes.as_deref(), &redactor);
+            let new_text = decode(name, new_bytes.as_deref(), &redactor);
+            TextDiff::from_lines(&old_text, &new_text)
+                .unified_diff()
+                .context_radius(3)
+                .header(&old_label, &new_label)
+                .to_string()
+                .trim_end_matches('\n')
+                .to_string()
+        };
+        changes.push(FileChange {
+            file_name: name.clone(),
+            kind,
+            diff,
+        });
+    }
+
+    Ok(SnapshotDiff {
+        old: old.label(),
+        new: new.label(),
+        changes,
+        unchanged,
+    })
+}
+
+fn backup_dir(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("target").join("backup")
+}
+
+/// 相対パス（`/` 区切り）→ 実際のファイルのパス
+fn source_files(
+    base_dir: &str,
+    source: &SnapshotSource,
+    live: &[FileInfo],
+) -> Result<BTreeMap<String, PathBuf>, String> {
+    let mut files = BTreeMap::new();
+    match source {
+        SnapshotSource::Live => {
+            for f in live {
+                files.insert(f.file_name.clone(), PathBuf::from(&f.file_url));
+            }
+        }
+        SnapshotSource::Snapshot(name) => {
+            let root = backup_dir(base_dir).join(name);
+            if !root.is_dir() {
+                return Err(format!("スナップショットが見つかりません: {}", root.display()));
+            }
+            walk_files(&root, "", &mut files)?;
+        }
+    }
+    Ok(files)
+}
+
+fn walk_files(dir: &Path, prefix: &str, files: &mut BTreeMap<String, PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("フォルダ読み込み失敗: {} ({})", e, dir.display()))?;
+    for entry in entries.flatten() {
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let path = entry.path();
+        if path.is_dir() {
+            walk_files(&path, &format!("{}/", name), files)?;
+        } else {
+            files.insert(name, path);
+        }
+    }
+    Ok(())
+}
+
+fn read_error(name: &str, e: std::io::Error) -> String {
+    format!("ファイル読み込み失敗: {} ({})", e, name)
+}
+
+/// 比較用のテキスト（BOM → 推定の順で読み、伏せ字にする）
+fn decode(name: &str, bytes: Option<&[u8]>, redactor: &Redactor) -> String {
+    let Some(bytes) = bytes else {
+        return String::new();
+    };
+    let mut text = decode_with_bom(bytes).unwrap_or_else(|| decode_guess(bytes)).text;
+    redactor.redact(name, &mut text);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テストごとの作業フォルダ（前回の残りは消してから作る）
+    fn project(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("text-read-snapdiff-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, content: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn snapshot(dir: &Path, snapshot: &str, name: &str, content: &[u8]) {
+        write(&dir.join("target").join("backup").join(snapshot).join(name), content);
+    }
+
+    fn live_file(dir: &Path, name: &str, content: &[u8]) -> FileInfo {
+        let path = dir.join(name);
+        write(&path, content);
+        FileInfo {
+            file_url: path.to_string_lossy().to_string(),
+            file_name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn kinds(diff: &SnapshotDiff) -> Vec<(&str, ChangeKind)> {
+        diff.changes.iter().map(|c| (c.file_name.as_str(), c.kind)).collect()
+    }
+
+    #[test]
+    fn compares_snapshot_with_live_files() {
+        let dir = project("live");
+        snapshot(&dir, "s1", "src/a.rs", b"fn a() {}\n");
+        snapshot(&dir, "s1", "src/b.rs", b"fn b() {}\n");
+        snapshot(&dir, "s1", "old.txt", b"old\n");
+        let live = vec![
+            live_file(&dir, "src/a.rs", b"fn a() {}\n"),
+            live_file(&dir, "src/b.rs", b"fn b() { 1 }\n"),
+            live_file(&dir, "new.txt", b"new\n"),
+        ];
+        let base = dir.to_string_lossy().to_string();
+        let diff = compare_snapshots(
+            &base,
+            &SnapshotSource::Snapshot("s1".to_string()),
+            &SnapshotSource::Live,
+            &live,
+            &CollectOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            kinds(&diff),
+            vec![
+                ("new.txt", ChangeKind::Added),
+                ("old.txt", ChangeKind::Removed),
+                ("src/b.rs", ChangeKind::Modified),
+            ]
+        );
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.summary(), "追加 1 / 削除 1 / 変更 1 / 変更なし 1");
+        assert_eq!(diff.new, "現在のファイル");
+        let modified = &diff.changes[2].diff;
+        assert!(modified.starts_with("--- a/src/b.rs\n+++ b/src/b.rs\n"), "{}", modified);
+        assert!(modified.contains("-fn b() {}\n+fn b() { 1 }"), "{}", modified);
+        assert!(diff.changes[0].diff.starts_with("--- /dev/null\n+++ b/new.txt"));
+        assert!(diff.changes[1].diff.starts_with("--- a/old.txt\n+++ /dev/null"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn binary_files_are_not_diffed() {
+        let dir = project("binary");
+        snapshot(&dir, "s1", "img.png", b"\x89PNG\x00\x01");
+        snapshot(&dir, "s2", "img.png", b"\x89PNG\x00\x02");
+        let base = dir.to_string_lossy().to_string();
+        let diff = compare_snapshots(
+            &base,
+            &SnapshotSource::Snapshot("s1".to_string()),
+            &SnapshotSource::Snapshot("s2".to_string()),
+            &[],
+            &CollectOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].diff, "Binary files a/img.png and b/img.png differ");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn secrets_are_redacted_on_both_sides() {
+        let dir = project("redact");
+        snapshot(&dir, "s1", "config.env", b"PASSWORD=hunter2\nA=1\n");
+        snapshot(&dir, "s2", "config.env", b"PASSWORD=swordfish\nA=2\n");
+        let base = dir.to_string_lossy().to_string();
+        let (old, new) = (SnapshotSource::Snapshot("s1".to_string()), SnapshotSource::Snapshot("s2".to_string()));
+
+        let diff = compare_snapshots(&base, &old, &new, &[], &CollectOptions::default()).unwrap();
+        let text = &diff.changes[0].diff;
+        assert!(!text.contains("hunter2") && !text.contains("swordfish"), "{}", text);
+        // 伏せ字にした行は両側で同じになる
+        assert!(!text.contains("-PASSWORD") && !text.contains("+PASSWORD"), "{}", text);
+
+        let options = CollectOptions {
+            keep_secrets: true,
+            ..Default::default()
+        };
+        let diff = compare_snapshots(&base, &old, &new, &[], &options).unwrap();
+        assert!(diff.changes[0].diff.contains("+PASSWORD=swordfish"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn resolves_snapshot_names() {
+        let dir = project("resolve");
+        for name in ["20250101_120000", "20250102_090000_before-refactor", "20250102_100000"] {
+            snapshot(&dir, name, "a.txt", b"a");
+        }
+        let base = dir.to_string_lossy().to_string();
+        let resolve = |name: &str| resolve_snapshot(&base, name);
+        let snap = |name: &str| Ok(SnapshotSource::Snapshot(name.to_string()));
+
+        assert_eq!(resolve("LIVE"), Ok(SnapshotSource::Live));
+        assert_eq!(resolve("latest"), snap("20250102_100000"));
+        assert_eq!(resolve("previous"), snap("20250102_090000_before-refactor"));
+        assert_eq!(resolve("20250101"), snap("20250101_120000"));
+        assert_eq!(resolve("20250102_10"), snap("20250102_100000"));
+        // 複数に一致する・どれにも一致しない名前はエラー
+        assert!(resolve("20250102").is_err());
+        assert!(resolve("2024").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn text_lists_changes_before_the_diff_block() {
+        let diff = SnapshotDiff {
+            old: "s1".to_string(),
+            new: "現在のファイル".to_string(),
+            changes: vec![FileChange {
+                file_name: "a.txt".to_string(),
+                kind: ChangeKind::Modified,
+                diff: "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-x\n+y".to_string(),
+            }],
+            unchanged: 2,
+        };
+        assert_eq!(
+            diff.to_text(),
+            "// Snapshot diff: s1 → 現在のファイル\n// 追加 0 / 削除 0 / 変更 1 / 変更なし 2\n\nM a.txt\n\n```diff\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-x\n+y\n```\n"
+        );
+
+        let empty = SnapshotDiff { changes: Vec::new(), ..diff };
+        assert_eq!(empty.to_text(), "// Snapshot diff: s1 → 現在のファイル\n// 追加 0 / 削除 0 / 変更 0 / 変更なし 2\n");
+    }
+}
@@ -0,0 +1,105 @@
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton, p50k_base_singleton, CoreBPE};
+
+use crate::models::ProjectOutput;
+
+/// トークン数を数えるエンコーディング（語彙はクレートに同梱されておりオフラインで動く）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TokenEncoding {
+    /// GPT-4 / GPT-3.5 系
+    #[default]
+    Cl100k,
+    /// GPT-4o 系
+    O200k,
+    /// Codex / GPT-3 系
+    P50k,
+    /// Claude 系の概算（公開語彙がないため cl100k_base で数える）
+    Claude,
+}
+
+impl TokenEncoding {
+    /// 設定ファイルの値（`TOKEN_ENCODING=`）から解釈する
+    pub fn from_name(name: &str) -> Option<TokenEncoding> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "cl100k_base" | "cl100k" | "gpt-4" | "gpt-3.5" => Some(TokenEncoding::Cl100k),
+            "o200k_base" | "o200k" | "gpt-4o" => Some(TokenEncoding::O200k),
+            "p50k_base" | "p50k" => Some(TokenEncoding::P50k),
+            "claude" => Some(TokenEncoding::Claude),
+            _ => None,
+        }
+    }
+
+    /// 設定ファイルに書く名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenEncoding::Cl100k => "cl100k_base",
+            TokenEncoding::O200k => "o200k_base",
+            TokenEncoding::P50k => "p50k_base",
+            TokenEncoding::Claude => "claude",
+        }
+    }
+
+    fn bpe(&self) -> &'static CoreBPE {
+        match self {
+            TokenEncoding::Cl100k | TokenEncoding::Claude => cl100k_base_singleton(),
+            TokenEncoding::O200k => o200k_base_singleton(),
+            TokenEncoding::P50k => p50k_base_singleton(),
+        }
+    }
+}
+
+/// テキストのトークン数を数える（特殊トークンも通常の文字列として扱う）
+pub fn count_tokens(text: &str, encoding: TokenEncoding) -> usize {
+    encoding.bpe().encode_ordinary(text).len()
+}
+
+/// 出力全体とファイルごとのトークン数
+#[derive(Clone, Debug)]
+pub struct TokenReport {
+    pub encoding: TokenEncoding,
+    /// コピー・出力されるテキスト全体のトークン数
+    pub total: usize,
+    /// (ファイル名, file_content のトークン数)。多い順
+    pub per_file: Vec<(String, usize)>,
+    pub budget: Option<usize>,
+}
+
+impl TokenReport {
+    /// 予算を超えているか
+    pub fn over_budget(&self) -> bool {
+        self.budget.is_some_and(|b| self.total > b)
+    }
+
+    /// "12,345 tokens (cl100k_base)" のような要約
+    pub fn summary(&self) -> String {
+        match self.budget {
+            Some(b) => format!("{} / {} tokens ({})", self.total, b, self.encoding.name()),
+            None => format!("{} tokens ({})", self.total, self.encoding.name()),
+        }
+    }
+}
+
+/// `rendered`（実際にコピー・出力するテキスト）全体と、各ファイルのトークン数を数える
+pub fn token_report(
+    output: &ProjectOutput,
+    rendered: &str,
+    encoding: TokenEncoding,
+    budget: Option<usize>,
+) -> TokenReport {
+    let mut per_file: Vec<(String, usize)> = output
+        .files
+        .iter()
+        .map(|f| {
+            // 収集時に数えてあればそれを使う（メタデータの tokens）
+            let count = f.tokens.unwrap_or_else(|| count_tokens(&f.file_content, encoding));
+            (f.file_name.clone(), count)
+        })
+        .collect();
+    per_file.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    TokenReport {
+        encoding,
+        total: count_tokens(rendered, encoding),
+        per_file,
+        budget,
+    }
+}
@@ -0,0 +1,289 @@
+use serde::Serialize;
+
+/// ツリーの要素の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Dir,
+    File,
+    /// 省略した要素のまとめ（`… (N files, M KB)`）
+    Omitted,
+}
+
+/// ディレクトリツリーの 1 要素（JSON ではネストしたオブジェクトになる）
+#[derive(Clone, Debug, Serialize)]
+pub struct TreeNode {
+    pub name: String,
+    /// ベースフォルダからの相対パス（ルートは空文字列）
+    pub path: String,
+    pub kind: NodeKind,
+    /// バイト数（フォルダは表示している中身の合計）
+    pub size: u64,
+    /// 推定トークン数（出力に含めたファイルは実際の数、それ以外はバイト数 / 4）
+    pub tokens: usize,
+    /// `files` に含まれているか（フォルダは中に含まれるファイルがあるか）
+    pub included: bool,
+    /// 警告付きで除外したファイルの理由（include パターンに一致しないだけのファイルは None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 省略したファイル数（`NodeKind::Omitted` のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omitted: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+/// ツリー表示の大きさの上限（None は無制限）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TreeLimits {
+    /// 表示する階層の深さ（`TREE_MAX_DEPTH=`。1 ならベースフォルダ直下まで）
+    pub max_depth: Option<usize>,
+    /// 1 フォルダに表示する要素数（`TREE_MAX_ENTRIES=`）
+    pub max_entries: Option<usize>,
+    /// 中のファイルがこの数を超えるフォルダは中身をまとめる（`TREE_COLLAPSE=`）
+    pub collapse_files: Option<usize>,
+    /// 出力に含めたファイルとその祖先のフォルダだけを表示する（`TREE_INCLUDED_ONLY=`）
+    pub included_only: bool,
+}
+
+/// ツリーに付けるファイルの状態
+#[derive(Clone, Debug)]
+pub enum FileStatus {
+    /// 出力に含めた（トークン数が分かっていればその数）
+    Included(Option<usize>),
+    /// 読み込もうとしたが警告付きで除外した（理由）
+    Skipped(String),
+    /// include パターンに一致しない
+    NotMatched,
+}
+
+impl TreeNode {
+    /// フォルダの要素を作る（大きさ等は `finish` で集計する）
+    pub fn dir(name: &str, path: &str) -> TreeNode {
+        TreeNode {
+            name: name.to_string(),
+            path: path.to_string(),
+            kind: NodeKind::Dir,
+            size: 0,
+            tokens: 0,
+            included: false,
+            reason: None,
+            omitted: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// `nodes` をまとめた要素を作る（`finish` 済みの要素に使う）
+    fn omitted(path: &str, nodes: &[TreeNode]) -> TreeNode {
+        TreeNode {
+            name: "…".to_string(),
+            path: path.to_string(),
+            kind: NodeKind::Omitted,
+            size: nodes.iter().map(|n| n.size).sum(),
+            tokens: nodes.iter().map(|n| n.tokens).sum(),
+            included: nodes.iter().any(|n| n.included),
+            reason: None,
+            omitted: Some(nodes.iter().map(TreeNode::file_count).sum()),
+            children: Vec::new(),
+        }
+    }
+
+    /// ファイルの要素を作る
+    pub fn file(name: &str, path: &str, size: u64, status: FileStatus) -> TreeNode {
+        let estimate = size.div_ceil(4) as usize;
+        let (included, tokens, reason) = match status {
+            FileStatus::Included(tokens) => (true, tokens.unwrap_or(estimate), None),
+            FileStatus::Skipped(reason) => (false, estimate, Some(reason)),
+            FileStatus::NotMatched => (false, estimate, None),
+        };
+        TreeNode {
+            name: name.to_string(),
+            path: path.to_string(),
+            kind: NodeKind::File,
+            size,
+            tokens,
+            included,
+            reason,
+            omitted: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// 中に含まれるファイル数（まとめた要素の分も数える）
+    pub fn file_count(&self) -> usize {
+        match self.kind {
+            NodeKind::File => 1,
+            NodeKind::Omitted => self.omitted.unwrap_or(0),
+            NodeKind::Dir => self.children.iter().map(TreeNode::file_count).sum(),
+        }
+    }
+
+    /// フォルダの大きさ・トークン数・included を子から集計する
+    pub fn finish(&mut self) {
+        if self.kind != NodeKind::Dir {
+            return;
+        }
+        for child in &mut self.children {
+            child.finish();
+        }
+        self.size = self.children.iter().map(|c| c.size).sum();
+        self.tokens = self.children.iter().map(|c| c.tokens).sum();
+        self.included = self.children.iter().any(|c| c.included);
+    }
+
+    /// 表示の上限を適用する（`finish` の後に呼ぶ。大きさ・トークン数はまとめる前の値のまま）
+    ///
+    /// `included_only` なら出力に含めていない要素を取り除き、深さの上限に達したフォルダと
+    /// ファイル数が `collapse_files` を超えるフォルダは中身を `… (N files, M KB)` の 1 行にまとめる。
+    /// `max_entries` を超えた分も同じようにまとめる。
+    pub fn apply_limits(&mut self, limits: &TreeLimits) {
+        self.limit_at(limits, 0);
+    }
+
+    fn limit_at(&mut self, limits: &TreeLimits, depth: usize) {
+        if self.kind != NodeKind::Dir {
+            return;
+        }
+        if limits.included_only {
+            self.children.retain(|c| c.included);
+        }
+        if self.children.is_empty() {
+            return;
+        }
+
+        let too_deep = limits.max_depth.is_some_and(|max| depth >= max);
+        // ベースフォルダ自体はまとめない
+        let too_many = depth > 0 && limits.collapse_files.is_some_and(|max| self.file_count() > max);
+        if too_deep || too_many {
+            self.children = vec![TreeNode::omitted(&self.path, &self.children)];
+            return;
+        }
+        if let Some(max) = limits.max_entries {
+            if self.children.len() > max {
+                let rest = self.children.split_off(max);
+                self.children.push(TreeNode::omitted(&self.path, &rest));
+            }
+        }
+        for child in &mut self.children {
+            child.limit_at(limits, depth + 1);
+        }
+    }
+
+    /// 罫線（`├──` / `└──`）で描いたテキストにする
+    ///
+    /// 出力に含めたファイルには `✓` と大きさ・推定トークン数を、警告付きで除外したファイルには
+    /// `✗` と理由を付ける。include パターンに一致しないだけのファイルは名前と大きさだけにする。
+    pub fn render_text(&self) -> String {
+        let mut out = format!("{}/\n", self.name);
+        push_children(&mut out, &self.children, "");
+        out.truncate(out.trim_end().len());
+        out
+    }
+}
+
+fn push_children(out: &mut String, children: &[TreeNode], indent: &str) {
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        out.push_str(indent);
+        out.push_str(if last { "└── " } else { "├── " });
+        out.push_str(&node_label(child));
+        out.push('\n');
+        if child.kind == NodeKind::Dir {
+            let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            push_children(out, &child.children, &indent);
+        }
+    }
+}
+
+/// 1 行分の表示
+fn node_label(node: &TreeNode) -> String {
+    match (node.kind, node.included, &node.reason) {
+        (NodeKind::Dir, _, _) => format!("{}/", node.name),
+        (NodeKind::Omitted, _, _) => format!(
+            "{} ({} files, {})",
+            node.name,
+            node.omitted.unwrap_or(0),
+            format_size(node.size)
+        ),
+        (NodeKind::File, true, _) => format!(
+            "{} ✓ ({}, ~{} tokens)",
+            node.name,
+            format_size(node.size),
+            format_count(node.tokens)
+        ),
+        (NodeKind::File, false, Some(reason)) => format!("{} ({}) ✗ {}", node.name, format_size(node.size), reason),
+        (NodeKind::File, false, None) => format!("{} ({})", node.name, format_size(node.size)),
+    }
+}
+
+/// 1536 → "1.5 KB"
+pub fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    let b = bytes as f64;
+    if b < KB {
+        format!("{} B", bytes)
+    } else if b < KB * KB {
+        format!("{:.1} KB", b / KB)
+    } else {
+        format!("{:.1} MB", b / KB / KB)
+    }
+}
+
+/// 3100 → "3.1k"
+fn format_count(n: usize) -> String {
+    if n < 1000 {
+        n.to_string()
+    } else {
+        format!("{:.1}k", n as f64 / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> TreeNode {
+        let mut src = TreeNode::dir("src", "src");
+        src.children.push(TreeNode::file("main.rs", "src/main.rs", 2048, FileStatus::Included(Some(500))));
+        src.children.push(TreeNode::file("big.rs", "src/big.rs", 100, FileStatus::Skipped("サイズ上限".to_string())));
+        let mut root = TreeNode::dir("proj", "");
+        root.children.push(src);
+        root.children.push(TreeNode::file("README.md", "README.md", 10, FileStatus::NotMatched));
+        root.finish();
+        root
+    }
+
+    #[test]
+    fn json_omits_the_reason_of_unmatched_files() {
+        let json = serde_json::to_value(sample()).unwrap();
+        let readme = &json["children"][1];
+        assert_eq!(readme["included"], false);
+        assert!(readme.get("reason").is_none());
+        assert_eq!(json["children"][0]["children"][1]["reason"], "サイズ上限");
+    }
+
+    #[test]
+    fn text_marks_included_and_skipped_files() {
+        assert_eq!(
+            sample().render_text(),
+            "proj/\n├── src/\n│   ├── main.rs ✓ (2.0 KB, ~500 tokens)\n│   └── big.rs (100 B) ✗ サイズ上限\n└── README.md (10 B)"
+        );
+    }
+
+    #[test]
+    fn folders_are_collapsed_but_not_the_root() {
+        let mut tree = sample();
+        tree.apply_limits(&TreeLimits {
+            collapse_files: Some(1),
+            ..TreeLimits::default()
+        });
+        assert_eq!(tree.render_text(), "proj/\n├── src/\n│   └── … (2 files, 2.1 KB)\n└── README.md (10 B)");
+
+        let mut tree = sample();
+        tree.apply_limits(&TreeLimits {
+            included_only: true,
+            ..TreeLimits::default()
+        });
+        assert_eq!(tree.file_count(), 1);
+    }
+}
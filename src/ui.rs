@@ -2,16 +2,19 @@ use fltk::prelude::*;
 use fltk::{
     button::{Button, CheckButton},
     dialog::alert,
-    enums::{Color, Font},
+    enums::{Color, Event, Font},
     frame::Frame,
     group::{Flex, Tabs, Group},
-    input::MultilineInput,
-    text::{TextBuffer, TextEditor, WrapMode},
+    input::{Input, MultilineInput},
+    menu::Choice,
+    text::{PositionType, TextBuffer, TextEditor, WrapMode},
     window::Window,
     app::{Sender, Receiver},
 };
 use std::{cell::RefCell, rc::Rc};
 
+use gpt_text_read::render::OutputFormat;
+
 use crate::app::AppData;
 
 /// メッセージ（イベント）
@@ -22,13 +25,25 @@ pub enum UiMessage {
     ExcludeFolder,
     SaveSettings,
     StartLoad,
-    LoadFinished(Result<crate::models::ProjectOutput, String>),
+    LoadFinished(Box<Result<gpt_text_read::ProjectOutput, String>>),
     Copy,
     UpdateCopySize(usize),
+    TokensCounted(gpt_text_read::tokens::TokenReport),
+    FormatChanged,
+    // ファイル内容タブで Ctrl+クリックした位置（path:line の参照を開く）
+    OpenReference(i32),
     ExportTxt,
+    // 出力先と出力するテキスト、CHUNK_UNIT で測った大きさ（別スレッドで測り終えたら送る）
+    ExportMeasured(std::path::PathBuf, String, usize),
     Backup,
+    // スナップショット比較タブ
+    CompareSnapshots,
+    CopySnapshotDiff,
 }
 
+/// 選び方のラベル（git_choice の順。0 は include パターン）
+pub const GIT_SELECTION_LABELS: [&str; 4] = ["パターン", "作業ツリーの変更", "ref以降の変更", "merge-base以降の変更"];
+
 /// GUI部品をまとめた構造体
 pub struct GuiComponents {
     pub win: Window,
@@ -36,9 +51,13 @@ pub struct GuiComponents {
     pub sender: Sender<UiMessage>,
 
     pub include_input: Rc<RefCell<MultilineInput>>,
+    // include パターンの代わりに git の変更から選ぶか（GIT_SELECTION_LABELS の順）と基準の ref
+    pub git_choice: Choice,
+    pub git_ref_input: Input,
     pub exclude_input: Rc<RefCell<MultilineInput>>,
 
     pub chosen_file_buffer: Rc<RefCell<TextBuffer>>,
+    pub chosen_file_editor: TextEditor,
     pub json_buffer: Rc<RefCell<TextBuffer>>,
     pub tree_buffer: Rc<RefCell<TextBuffer>>,
     pub dev_memo_buffer: Rc<RefCell<TextBuffer>>,
@@ -49,8 +68,22 @@ pub struct GuiComponents {
     // 追加: ログ表示用バッファ
     pub log_buffer: Rc<RefCell<TextBuffer>>,
 
+    // スナップショット比較タブ（比較元・比較先の選択と結果）
+    pub snap_old_choice: Choice,
+    pub snap_new_choice: Choice,
+    pub snapdiff_buffer: Rc<RefCell<TextBuffer>>,
+
     pub tree_check_state: Rc<RefCell<bool>>,
+    // ignore ファイル（.gitignore 等）に従うか
+    pub ignore_check: CheckButton,
+    // ファイル追加・フォルダ除外でグロブ形式のパターンを作るか
+    pub glob_check: CheckButton,
+    // file_content に行番号を付けるか
+    pub line_number_check: CheckButton,
+    // コピー・テキスト出力の形式（OutputFormat::ALL の順）
+    pub format_choice: Choice,
     pub copy_size_label: Frame,
+    pub token_label: Frame,
 }
 
 /// GUI を生成して GuiComponents を返す
@@ -77,7 +110,25 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
     add_file_btn.set_label_size(14);
     add_file_btn.set_label_color(Color::Black);
     add_file_btn.set_label_font(Font::HelveticaBold);
-    left_flex.fixed(&add_file_btn, 30);
+
+    // include パターンの代わりに git の変更から選ぶモード（GIT_CHANGED=）
+    let mut git_choice = Choice::default();
+    for label in GIT_SELECTION_LABELS {
+        git_choice.add_choice(label);
+    }
+    git_choice.set_value(0);
+    git_choice.set_tooltip("収集するファイルの選び方（git の変更を使う場合は include パターンを使わない）");
+
+    let mut git_ref_input = Input::default();
+    git_ref_input.set_tooltip("「ref以降の変更」「merge-base以降の変更」の基準にするブランチ・タグ・コミット");
+
+    let mut select_flex = Flex::default().row();
+    select_flex.set_spacing(5);
+    select_flex.add(&add_file_btn);
+    select_flex.add(&git_choice);
+    select_flex.add(&git_ref_input);
+    select_flex.end();
+    left_flex.fixed(&select_flex, 30);
 
     let include_input = Rc::new(RefCell::new(MultilineInput::new(0, 0, 0, 0, "")));
     include_input.borrow_mut().set_readonly(false);
@@ -140,20 +191,42 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
         });
     }
 
+    let mut ignore_check = CheckButton::default().with_label("ignore反映");
+    ignore_check.set_tooltip(".gitignore / .git/info/exclude / .textreadignore に従って除外");
+
+    let mut glob_check = CheckButton::default().with_label("Glob形式");
+    glob_check.set_tooltip("ファイル追加・フォルダ除外で GLOB: 形式のパターンを追加");
+
+    let mut line_number_check = CheckButton::default().with_label("行番号");
+    line_number_check.set_tooltip("ファイル内容に行番号を付けて出力");
+
+    let mut format_choice = Choice::default();
+    for format in OutputFormat::ALL {
+        format_choice.add_choice(format.label());
+    }
+    format_choice.set_value(0);
+    format_choice.set_tooltip("コピー・テキスト出力の形式");
+
     let mut update_btn = Button::default().with_label("保存更新");
     update_btn.set_label_size(14);
     update_btn.set_label_color(Color::Black);
     update_btn.set_label_font(Font::HelveticaBold);
 
     let copy_size_label = Frame::default().with_label("Copy Size: 0");
+    let token_label = Frame::default().with_label("Tokens: 0");
 
     btn_flex.add(&project_btn);
     btn_flex.add(&copy_btn);
     btn_flex.add(&backup_btn);
     btn_flex.add(&export_btn);
     btn_flex.add(&tree_check);
+    btn_flex.add(&ignore_check);
+    btn_flex.add(&glob_check);
+    btn_flex.add(&line_number_check);
+    btn_flex.add(&format_choice);
     btn_flex.add(&update_btn);
     btn_flex.add(&copy_size_label);
+    btn_flex.add(&token_label);
     btn_flex.end();
 
     main_flex.fixed(&btn_flex, 40);
@@ -169,16 +242,16 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
     let dev_memo_buffer = Rc::new(RefCell::new(TextBuffer::default()));
     let llm_buffer = Rc::new(RefCell::new(TextBuffer::default()));
     let log_buffer = Rc::new(RefCell::new(TextBuffer::default())); // ログ用
+    let snapdiff_buffer = Rc::new(RefCell::new(TextBuffer::default()));
 
     let tabs = Tabs::new(0, 0, 1000, 300, "");
 
     // --- ファイル内容タブ
     let grp_text = Group::new(0, 25, 1000, 275, "ファイル内容");
-    {
-        let mut chosen_file_editor = TextEditor::new(5, 30, 990, 260, "");
-        chosen_file_editor.set_buffer(chosen_file_buffer.borrow().clone());
-        chosen_file_editor.wrap_mode(WrapMode::AtBounds, 0);
-    }
+    let mut chosen_file_editor = TextEditor::new(5, 30, 990, 260, "");
+    chosen_file_editor.set_buffer(chosen_file_buffer.borrow().clone());
+    chosen_file_editor.wrap_mode(WrapMode::AtBounds, 0);
+    chosen_file_editor.set_tooltip("Ctrl+クリックで path:line の参照先へ移動");
     grp_text.end();
 
     // --- ツリー内容タブ
@@ -190,8 +263,8 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
     }
     grp_tree.end();
 
-    // --- JSONデータタブ
-    let grp_json = Group::new(0, 25, 1000, 275, "JSONデータ");
+    // --- 出力データタブ（JSON / Markdown 等、選択中の形式）
+    let grp_json = Group::new(0, 25, 1000, 275, "出力データ");
     {
         let mut json_editor = TextEditor::new(5, 30, 990, 260, "");
         json_editor.set_buffer(json_buffer.borrow().clone());
@@ -217,6 +290,19 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
     }
     grp_llm.end();
 
+    // --- スナップショット比較タブ
+    let grp_snap = Group::new(0, 25, 1000, 275, "スナップショット比較");
+    let snap_old_choice = Choice::new(60, 30, 300, 25, "比較元");
+    let snap_new_choice = Choice::new(420, 30, 300, 25, "比較先");
+    let mut snap_compare_btn = Button::new(730, 30, 100, 25, "比較");
+    let mut snap_copy_btn = Button::new(840, 30, 120, 25, "差分コピー");
+    {
+        let mut snapdiff_editor = TextEditor::new(5, 60, 990, 230, "");
+        snapdiff_editor.set_buffer(snapdiff_buffer.borrow().clone());
+        snapdiff_editor.wrap_mode(WrapMode::AtBounds, 0);
+    }
+    grp_snap.end();
+
     // --- ログタブ (追加)
     let grp_log = Group::new(0, 25, 1000, 275, "ログ");
     {
@@ -275,12 +361,46 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
             sender.send(UiMessage::StartLoad);
         });
     }
+    {
+        // LLM の回答を貼り付けた src/app.rs:42 のような参照を Ctrl+クリックで開く
+        let sender = s;
+        chosen_file_editor.handle(move |ed, ev| {
+            if ev == Event::Push && fltk::app::is_event_ctrl() {
+                let pos = ed.xy_to_position(
+                    fltk::app::event_x(),
+                    fltk::app::event_y(),
+                    PositionType::Cursor,
+                );
+                sender.send(UiMessage::OpenReference(pos));
+                return true;
+            }
+            false
+        });
+    }
+    {
+        let sender = s;
+        format_choice.set_callback(move |_| {
+            sender.send(UiMessage::FormatChanged);
+        });
+    }
     {
         let sender = s.clone();
         backup_btn.set_callback(move |_| {
             sender.send(UiMessage::Backup);
         });
     }
+    {
+        let sender = s;
+        snap_compare_btn.set_callback(move |_| {
+            sender.send(UiMessage::CompareSnapshots);
+        });
+    }
+    {
+        let sender = s;
+        snap_copy_btn.set_callback(move |_| {
+            sender.send(UiMessage::CopySnapshotDiff);
+        });
+    }
 
     GuiComponents {
         win,
@@ -288,9 +408,12 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
         sender: s,
 
         include_input,
+        git_choice,
+        git_ref_input,
         exclude_input,
 
         chosen_file_buffer,
+        chosen_file_editor,
         json_buffer,
         tree_buffer,
         dev_memo_buffer,
@@ -299,8 +422,17 @@ pub fn build_ui(_app_data: Rc<AppData>) -> GuiComponents {
 
         log_buffer,
 
+        snap_old_choice,
+        snap_new_choice,
+        snapdiff_buffer,
+
         tree_check_state,
+        ignore_check,
+        glob_check,
+        line_number_check,
+        format_choice,
         copy_size_label,
+        token_label,
     }
 }
 